@@ -0,0 +1,5 @@
+// Quantities derived from atmospheric sensor readings, independent of the
+// bus and the driver that produced them.
+pub mod psychrometrics;
+pub mod altitude;
+pub mod forecast;
@@ -0,0 +1,116 @@
+// Barometric altitude and sea-level pressure reduction, in Pa and metres,
+// following the International Standard Atmosphere troposphere (below 11 km).
+
+use libm::pow;
+
+use crate::sensors::bme280::BME280Measurement;
+
+pub const STANDARD_SEA_LEVEL_PRESSURE: f64 = 101_325.0;  // Pa
+const STANDARD_TEMPERATURE: f64 = 288.15;  // K at sea level
+const LAPSE_RATE: f64 = 0.0065;  // K/m
+// g·M/(R·L), the barometric formula exponent
+const EXPONENT: f64 = 5.255_877;
+const CELSIUS_TO_KELVIN: f64 = 273.15;
+
+// Altitude in m above the level where the pressure is `sea_level_pressure`,
+// international barometric formula.
+pub fn altitude(pressure: f64, sea_level_pressure: f64) -> f64 {
+    STANDARD_TEMPERATURE / LAPSE_RATE * (1.0 - pow(pressure / sea_level_pressure, 1.0 / EXPONENT))
+}
+
+// Pressure at `altitude` for a given sea-level pressure, inverse of `altitude`
+pub fn pressure_at_altitude(altitude: f64, sea_level_pressure: f64) -> f64 {
+    sea_level_pressure * pow(1.0 - LAPSE_RATE * altitude / STANDARD_TEMPERATURE, EXPONENT)
+}
+
+// Reduce station pressure to sea level from the station altitude
+// in m and the measured temperature in °C, assuming the standard lapse rate
+// in the air column below the station.
+pub fn sea_level_pressure(pressure: f64, altitude: f64, temperature: f64) -> f64 {
+    let column = LAPSE_RATE * altitude;
+    pressure * pow(1.0 - column / (temperature + column + CELSIUS_TO_KELVIN), -EXPONENT)
+}
+
+// Altitude against a configurable sea-level reference, e.g. the QNH
+// published by a nearby airport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Altimeter {
+    pub sea_level_pressure: f64  // Pa
+}
+
+impl Default for Altimeter {
+    fn default() -> Self {
+        Altimeter { sea_level_pressure: STANDARD_SEA_LEVEL_PRESSURE }
+    }
+}
+
+impl Altimeter {
+    pub fn new(sea_level_pressure: f64) -> Altimeter {
+        Altimeter { sea_level_pressure }
+    }
+
+    // Reference that puts the current `pressure` at a known altitude
+    pub fn calibrated(pressure: f64, altitude: f64) -> Altimeter {
+        let ratio = pressure_at_altitude(altitude, STANDARD_SEA_LEVEL_PRESSURE) / STANDARD_SEA_LEVEL_PRESSURE;
+        Altimeter { sea_level_pressure: pressure / ratio }
+    }
+
+    pub fn altitude(&self, pressure: f64) -> f64 {
+        altitude(pressure, self.sea_level_pressure)
+    }
+
+    // None when pressure was skipped
+    pub fn altitude_of(&self, measurement: &BME280Measurement) -> Option<f64> {
+        Some(self.altitude(measurement.pressure?))
+    }
+}
+
+// Sea-level pressure for a station at a known altitude, None when pressure or
+// temperature was skipped.
+pub fn sea_level_pressure_of(measurement: &BME280Measurement, altitude: f64) -> Option<f64> {
+    Some(sea_level_pressure(measurement.pressure?, altitude, measurement.temperature?))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use libm::fabs;
+
+    use super::*;
+
+    fn assert_close(value: f64, expected: f64, tolerance: f64) {
+        assert!(fabs(value - expected) <= tolerance, "{value} is not within {tolerance} of {expected}");
+    }
+
+    #[test]
+    fn standard_atmosphere_table() {
+        // ISA: 89 875 Pa at 1000 m, 54 020 Pa at 5000 m
+        assert_close(altitude(STANDARD_SEA_LEVEL_PRESSURE, STANDARD_SEA_LEVEL_PRESSURE), 0.0, 1e-9);
+        assert_close(altitude(89_875.0, STANDARD_SEA_LEVEL_PRESSURE), 1000.0, 1.0);
+        assert_close(altitude(54_020.0, STANDARD_SEA_LEVEL_PRESSURE), 5000.0, 2.0);
+        assert_close(pressure_at_altitude(1000.0, STANDARD_SEA_LEVEL_PRESSURE), 89_875.0, 5.0);
+    }
+
+    #[test]
+    fn sea_level_reduction() {
+        // A standard atmosphere station at 1000 m, 8.5 °C, reduces to 1013.25 hPa
+        assert_close(sea_level_pressure(89_875.0, 1000.0, 8.5), STANDARD_SEA_LEVEL_PRESSURE, 10.0);
+        // Warmer air columns are lighter, so need less correction
+        assert!(sea_level_pressure(89_875.0, 1000.0, 25.0) < sea_level_pressure(89_875.0, 1000.0, 0.0));
+        assert_eq!(sea_level_pressure(100_000.0, 0.0, 15.0), 100_000.0);
+    }
+
+    #[test]
+    fn altimeter_reference() {
+        let altimeter = Altimeter::calibrated(95_000.0, 420.0);
+        assert_close(altimeter.altitude(95_000.0), 420.0, 1e-6);
+        assert!(altimeter.sea_level_pressure > 95_000.0);
+
+        let low = Altimeter::new(99_000.0);
+        assert!(low.altitude(95_000.0) < Altimeter::default().altitude(95_000.0));
+
+        let measurement = BME280Measurement { temperature: Some(15.0), pressure: None, humidity: None };
+        assert_eq!(altimeter.altitude_of(&measurement), None);
+        assert_eq!(sea_level_pressure_of(&measurement, 420.0), None);
+    }
+}
@@ -0,0 +1,394 @@
+// Pressure tendency over the last three hours and a Zambretti forecast from
+// it. Feed it sea-level pressure (see `altitude::sea_level_pressure`, or
+// `record` which reduces for you) so the forecast bands apply whatever the
+// station altitude.
+
+use core::time::Duration;
+
+use libm::fabs;
+
+use crate::atmosphere::altitude::sea_level_pressure_of;
+use crate::sensors::bme280::BME280Measurement;
+
+// Synoptic observations report the change over three hours
+pub const TENDENCY_PERIOD: Duration = Duration::from_secs(3 * 3600);
+
+// Changes below this in hPa count as none when describing the shape of the
+// tendency, the resolution synoptic reports use.
+const SAME: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    at: Duration,
+    pressure: f64  // Pa
+}
+
+// Rolling window of the last N pressure readings, with timestamps from any
+// monotonic clock. Size it to cover at least `TENDENCY_PERIOD`, e.g. 37
+// samples at one per five minutes.
+#[derive(Debug, Clone)]
+pub struct PressureHistory<const N: usize> {
+    samples: [Sample; N],
+    len: usize,
+    next: usize
+}
+
+impl<const N: usize> Default for PressureHistory<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> PressureHistory<N> {
+    pub fn new() -> Self {
+        PressureHistory { samples: [Sample { at: Duration::ZERO, pressure: 0.0 }; N], len: 0, next: 0 }
+    }
+
+    // Add a reading in Pa, dropping the oldest once full. A reading older
+    // than the latest one means the clock restarted, so the history does too.
+    pub fn push(&mut self, at: Duration, pressure: f64) {
+        if N == 0 {
+            return
+        }
+        if self.latest().is_some_and(|latest| at < latest.at) {
+            self.clear();
+        }
+        self.samples[self.next] = Sample { at, pressure };
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    // Record a measurement taken `altitude` metres above sea level, reduced
+    // to sea level. Measurements without pressure or temperature are ignored.
+    pub fn record(&mut self, at: Duration, measurement: &BME280Measurement, altitude: f64) {
+        if let Some(pressure) = sea_level_pressure_of(measurement, altitude) {
+            self.push(at, pressure);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Latest pressure in Pa
+    pub fn pressure(&self) -> Option<f64> {
+        self.latest().map(|sample| sample.pressure)
+    }
+
+    // Pressure at a past instant, interpolated between the readings around it
+    pub fn pressure_at(&self, at: Duration) -> Option<f64> {
+        let mut previous: Option<Sample> = None;
+        for sample in self.iter() {
+            if sample.at == at {
+                return Some(sample.pressure)
+            }
+            if sample.at > at {
+                let before = previous?;
+                let span = (sample.at - before.at).as_secs_f64();
+                let fraction = (at - before.at).as_secs_f64() / span;
+                return Some(before.pressure + (sample.pressure - before.pressure) * fraction)
+            }
+            previous = Some(sample);
+        }
+        None
+    }
+
+    // Tendency over the last three hours, None until the window covers them
+    pub fn tendency(&self) -> Option<Tendency> {
+        let latest = self.latest()?;
+        let start = latest.at.checked_sub(TENDENCY_PERIOD)?;
+        let start_pressure = self.pressure_at(start)?;
+        let middle_pressure = self.pressure_at(start + TENDENCY_PERIOD / 2)?;
+
+        Some(Tendency::new(
+            (middle_pressure - start_pressure) / 100.0,
+            (latest.pressure - middle_pressure) / 100.0
+        ))
+    }
+
+    // Zambretti forecast from the latest pressure and the tendency
+    pub fn forecast(&self) -> Option<Forecast> {
+        let tendency = self.tendency()?;
+        Some(zambretti(self.pressure()?, tendency.trend()))
+    }
+
+    fn latest(&self) -> Option<Sample> {
+        self.iter().last()
+    }
+
+    // Samples from the oldest to the latest
+    fn iter(&self) -> impl Iterator<Item = Sample> + '_ {
+        (0..self.len).map(move |i| self.samples[(self.next + N - self.len + i) % N])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    Rising,
+    Steady,
+    Falling
+}
+
+// Met Office terms for the size of a three hour change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rate {
+    Steady,  // Under 0.1 hPa
+    Slowly,  // 0.1 to 1.5 hPa
+    Moderately,  // 1.6 to 3.5 hPa, plain "rising" or "falling"
+    Quickly,  // 3.6 to 6.0 hPa
+    VeryRapidly  // Over 6.0 hPa
+}
+
+impl Rate {
+    fn from_change(change: f64) -> Rate {
+        // Reports round the change to 0.1 hPa
+        match fabs(change) {
+            c if c < 0.05 => Rate::Steady,
+            c if c < 1.55 => Rate::Slowly,
+            c if c < 3.55 => Rate::Moderately,
+            c if c < 6.05 => Rate::Quickly,
+            _ => Rate::VeryRapidly
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tendency {
+    pub change: f64,  // hPa over three hours
+    pub rate: Rate,
+    pub code: u8  // WMO code table 0200, characteristic of pressure tendency
+}
+
+impl Tendency {
+    // From the change in hPa over each half of the period
+    pub fn new(first_half: f64, second_half: f64) -> Tendency {
+        let change = first_half + second_half;
+        Tendency { change, rate: Rate::from_change(change), code: wmo_code(first_half, second_half) }
+    }
+
+    // Slow changes are forecast as steady, as on a barometer dial
+    pub fn trend(&self) -> Trend {
+        match self.rate {
+            Rate::Steady | Rate::Slowly => Trend::Steady,
+            _ if self.change > 0.0 => Trend::Rising,
+            _ => Trend::Falling
+        }
+    }
+
+    // Falling quickly or faster: a gale or storm is likely on its way
+    pub fn storm_warning(&self) -> bool {
+        self.change < 0.0 && self.rate >= Rate::Quickly
+    }
+}
+
+// Characteristic from the shape of the curve: 0 to 3 end higher than three
+// hours ago, 4 is steady, 5 to 8 end lower (0 and 5 may also end the same).
+fn wmo_code(first_half: f64, second_half: f64) -> u8 {
+    let rises = |change: f64| change >= SAME;
+    let falls = |change: f64| change <= -SAME;
+    let change = first_half + second_half;
+
+    if fabs(change) < SAME {
+        return match (rises(first_half), falls(first_half)) {
+            (true, _) if falls(second_half) => 0,
+            (_, true) if rises(second_half) => 5,
+            _ => 4
+        }
+    }
+    if change > 0.0 {
+        if rises(first_half) && falls(second_half) {
+            0
+        } else if rises(first_half) && second_half < first_half / 2.0 {
+            1  // Increasing, then steady or more slowly
+        } else if !rises(first_half) || second_half > first_half * 2.0 {
+            3  // Decreasing or steady, then increasing; or increasing more rapidly
+        } else {
+            2
+        }
+    } else if falls(first_half) && rises(second_half) {
+        5
+    } else if falls(first_half) && second_half > first_half / 2.0 {
+        6  // Decreasing, then steady or more slowly
+    } else if !falls(first_half) || second_half < first_half * 2.0 {
+        8  // Steady or increasing, then decreasing; or decreasing more rapidly
+    } else {
+        7
+    }
+}
+
+// Negretti and Zambra's forecaster, letters A (settled) to Z (stormy)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Forecast {
+    pub letter: char,
+    pub text: &'static str
+}
+
+const FORECASTS: [&str; 26] = [
+    "Settled fine", "Fine weather", "Becoming fine", "Fine, becoming less settled", "Fine, possible showers",
+    "Fairly fine, improving", "Fairly fine, possible showers early", "Fairly fine, showery later",
+    "Showery early, improving", "Changeable, mending", "Fairly fine, showers likely",
+    "Rather unsettled clearing later", "Unsettled, probably improving", "Showery, bright intervals",
+    "Showery, becoming less settled", "Changeable, some rain", "Unsettled, short fine intervals",
+    "Unsettled, rain later", "Unsettled, some rain", "Mostly very unsettled", "Occasional rain, worsening",
+    "Rain at times, very unsettled", "Rain at frequent intervals", "Rain, very unsettled",
+    "Stormy, may improve", "Stormy, much rain"
+];
+
+// Forecast index for each of 22 pressure bands from 950 to 1050 hPa, lowest first
+const RISING: [u8; 22] = [25, 25, 25, 24, 24, 19, 16, 12, 11, 9, 8, 6, 5, 2, 1, 1, 0, 0, 0, 0, 0, 0];
+const STEADY: [u8; 22] = [25, 25, 25, 25, 25, 25, 23, 23, 22, 18, 15, 13, 10, 4, 1, 1, 0, 0, 0, 0, 0, 0];
+const FALLING: [u8; 22] = [25, 25, 25, 25, 25, 25, 25, 25, 23, 23, 21, 20, 17, 14, 7, 3, 1, 1, 1, 0, 0, 0];
+
+const ZAMBRETTI_LOW: f64 = 950.0;  // hPa
+const ZAMBRETTI_HIGH: f64 = 1050.0;
+
+// Forecast for the next few hours from sea-level pressure in Pa. Pressure
+// outside 950 to 1050 hPa is read as the nearest end of the scale.
+pub fn zambretti(sea_level_pressure: f64, trend: Trend) -> Forecast {
+    let table = match trend {
+        Trend::Rising => &RISING,
+        Trend::Steady => &STEADY,
+        Trend::Falling => &FALLING
+    };
+    let band_width = (ZAMBRETTI_HIGH - ZAMBRETTI_LOW) / table.len() as f64;
+    let band = ((sea_level_pressure / 100.0 - ZAMBRETTI_LOW) / band_width).clamp(0.0, (table.len() - 1) as f64) as usize;
+
+    let index = table[band];
+    Forecast { letter: char::from(b'A' + index), text: FORECASTS[usize::from(index)] }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    // Readings every ten minutes over three hours, pressure in hPa from `curve`
+    fn series<const N: usize>(curve: impl Fn(f64) -> f64) -> PressureHistory<N> {
+        let mut history = PressureHistory::new();
+        for step in 0..=18u32 {
+            let hours = f64::from(step) / 6.0;
+            history.push(MINUTE * step * 10, curve(hours) * 100.0);
+        }
+        history
+    }
+
+    #[test]
+    fn needs_three_hours() {
+        let mut history = PressureHistory::<64>::new();
+        history.push(Duration::ZERO, 101_300.0);
+        history.push(MINUTE * 120, 101_000.0);
+        assert_eq!(history.tendency(), None);
+        assert_eq!(history.forecast(), None);
+
+        history.push(MINUTE * 180, 100_900.0);
+        assert!(history.tendency().is_some());
+    }
+
+    #[test]
+    fn window_drops_oldest() {
+        let history = series::<8>(|hours| 1000.0 + hours);
+        assert_eq!(history.len(), 8);
+        assert_eq!(history.tendency(), None);
+        assert_eq!(history.pressure(), Some(100_300.0));
+    }
+
+    #[test]
+    fn interpolates_between_readings() {
+        let mut history = PressureHistory::<4>::new();
+        history.push(MINUTE * 10, 100_000.0);
+        history.push(MINUTE * 20, 100_100.0);
+        assert_eq!(history.pressure_at(MINUTE * 15), Some(100_050.0));
+        assert_eq!(history.pressure_at(MINUTE * 5), None);
+
+        // A clock going backwards starts over
+        history.push(MINUTE, 99_000.0);
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn steady_pressure() {
+        let tendency = series::<32>(|_| 1013.0).tendency().unwrap();
+        assert_eq!((tendency.rate, tendency.code, tendency.trend()), (Rate::Steady, 4, Trend::Steady));
+        assert!(!tendency.storm_warning());
+    }
+
+    #[test]
+    fn wmo_characteristics() {
+        let code = |curve: fn(f64) -> f64| series::<32>(curve).tendency().unwrap().code;
+
+        assert_eq!(code(|h| 1010.0 + h), 2);  // Steady rise
+        assert_eq!(code(|h| 1010.0 + 2.0 * h.min(1.5)), 1);  // Rising, then steady
+        assert_eq!(code(|h| 1010.0 + 2.0 * (h - 1.5).max(0.0)), 3);  // Steady, then rising
+        assert_eq!(code(|h| 1010.0 + 1.5 - fabs(h - 1.5) * 0.5), 0);  // Up then down, back to the same
+        assert_eq!(code(|h| 1010.0 - h), 7);  // Steady fall
+        assert_eq!(code(|h| 1010.0 - 2.0 * h.min(1.5)), 6);  // Falling, then steady
+        assert_eq!(code(|h| 1010.0 - 2.0 * (h - 1.5).max(0.0)), 8);  // Steady, then falling
+        assert_eq!(code(|h| 1010.0 + fabs(h - 1.5)), 5);  // Down then up
+    }
+
+    #[test]
+    fn storm_warning_on_rapid_fall() {
+        let tendency = series::<32>(|h| 1005.0 - 2.5 * h).tendency().unwrap();
+        assert!(fabs(tendency.change + 7.5) < 1e-9);
+        assert_eq!((tendency.rate, tendency.trend()), (Rate::VeryRapidly, Trend::Falling));
+        assert!(tendency.storm_warning());
+
+        let rising = series::<32>(|h| 995.0 + 2.5 * h).tendency().unwrap();
+        assert!(!rising.storm_warning());
+
+        let slow = series::<32>(|h| 1005.0 - 0.4 * h).tendency().unwrap();
+        assert_eq!((slow.rate, slow.trend()), (Rate::Slowly, Trend::Steady));
+    }
+
+    #[test]
+    fn zambretti_letters() {
+        assert_eq!(zambretti(103_000.0, Trend::Rising), Forecast { letter: 'A', text: "Settled fine" });
+        assert_eq!(zambretti(100_200.0, Trend::Steady), Forecast { letter: 'N', text: "Showery, bright intervals" });
+        assert_eq!(zambretti(100_200.0, Trend::Falling).letter, 'U');
+        assert_eq!(zambretti(96_000.0, Trend::Falling), Forecast { letter: 'Z', text: "Stormy, much rain" });
+        // Off the scale readings use its ends
+        assert_eq!(zambretti(110_000.0, Trend::Falling).letter, 'A');
+        assert_eq!(zambretti(90_000.0, Trend::Rising).letter, 'Z');
+    }
+
+    #[test]
+    fn forecast_from_history() {
+        let falling = series::<32>(|h| 1002.0 - h).forecast().unwrap();
+        let rising = series::<32>(|h| 996.0 + h).forecast().unwrap();
+        assert!(falling.letter > rising.letter);
+    }
+
+    #[test]
+    fn records_sea_level_pressure() {
+        use crate::atmosphere::altitude::{pressure_at_altitude, STANDARD_SEA_LEVEL_PRESSURE};
+
+        // Steady standard atmosphere, seen from 300 m
+        let station = BME280Measurement {
+            temperature: Some(13.05),
+            pressure: Some(pressure_at_altitude(300.0, STANDARD_SEA_LEVEL_PRESSURE)),
+            humidity: None
+        };
+        let mut history = PressureHistory::<32>::new();
+        for step in 0..=18u32 {
+            history.record(MINUTE * step * 10, &station, 300.0);
+        }
+        history.record(MINUTE * 190, &BME280Measurement { temperature: None, ..station }, 300.0);
+
+        assert_eq!(history.len(), 19);
+        assert!(fabs(history.pressure().unwrap() - STANDARD_SEA_LEVEL_PRESSURE) < 10.0);
+        // Station pressure would put it in the unsettled bands
+        assert_eq!(history.forecast(), Some(zambretti(STANDARD_SEA_LEVEL_PRESSURE, Trend::Steady)));
+        assert_ne!(history.forecast(), Some(zambretti(station.pressure.unwrap(), Trend::Steady)));
+    }
+}
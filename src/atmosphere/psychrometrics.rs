@@ -0,0 +1,160 @@
+// Moisture quantities derived from a temperature and relative humidity
+// reading. Plain f64 maths through libm, so they work without `std`.
+
+use libm::{exp, fabs, log, sqrt};
+
+use crate::sensors::bme280::BME280Measurement;
+
+const CELSIUS_TO_KELVIN: f64 = 273.15;
+
+// Saturation vapour pressure over water in hPa, Arden Buck (1996).
+// Within 0.05% of the Wexler tables from -40 to 50 °C.
+pub fn saturation_vapour_pressure(temperature: f64) -> f64 {
+    6.1121 * exp((18.678 - temperature / 234.5) * (temperature / (257.14 + temperature)))
+}
+
+// Partial pressure of water vapour in hPa
+pub fn vapour_pressure(temperature: f64, humidity: f64) -> f64 {
+    humidity / 100.0 * saturation_vapour_pressure(temperature)
+}
+
+// Dew point in °C, Magnus formula with Sonntag (1990) constants. Within
+// 0.35 °C from -45 to 60 °C, undefined at 0 %RH.
+pub fn dew_point(temperature: f64, humidity: f64) -> f64 {
+    const B: f64 = 17.62;
+    const C: f64 = 243.12;
+    let gamma = log(humidity / 100.0) + B * temperature / (C + temperature);
+    C * gamma / (B - gamma)
+}
+
+// Mass of water vapour per volume of air in g/m³, from the ideal gas law
+pub fn absolute_humidity(temperature: f64, humidity: f64) -> f64 {
+    // 100 Pa/hPa · 1000 g/kg / 461.5 J/(kg·K), the specific gas constant of water vapour
+    216.7 * vapour_pressure(temperature, humidity) / (temperature + CELSIUS_TO_KELVIN)
+}
+
+// Vapour pressure deficit in kPa: how far the air is from saturation
+pub fn vapour_pressure_deficit(temperature: f64, humidity: f64) -> f64 {
+    saturation_vapour_pressure(temperature) * (1.0 - humidity / 100.0) / 10.0
+}
+
+// Environment Canada humidex (Masterton and Richardson, 1979). Only
+// meaningful above 20 °C with a dew point above 0 °C.
+pub fn humidex(temperature: f64, humidity: f64) -> f64 {
+    let dew_point = dew_point(temperature, humidity) + CELSIUS_TO_KELVIN;
+    let vapour_pressure = 6.11 * exp(5417.7530 * (1.0 / 273.16 - 1.0 / dew_point));
+    temperature + 0.5555 * (vapour_pressure - 10.0)
+}
+
+// NWS heat index in °C: Steadman's simple formula, switching to the
+// Rothfusz regression with its low and high humidity adjustments from 80 °F
+// (26.7 °C). Not defined below about 20 °C.
+pub fn heat_index(temperature: f64, humidity: f64) -> f64 {
+    let t = temperature * 9.0 / 5.0 + 32.0;
+    let rh = humidity;
+
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    let index = if (simple + t) / 2.0 < 80.0 {
+        simple
+    } else {
+        let rothfusz = -42.379 + 2.04901523 * t + 10.14333127 * rh - 0.22475541 * t * rh - 0.00683783 * t * t
+            - 0.05481717 * rh * rh + 0.00122874 * t * t * rh + 0.00085282 * t * rh * rh - 0.00000199 * t * t * rh * rh;
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            rothfusz - (13.0 - rh) / 4.0 * sqrt((17.0 - fabs(t - 95.0)) / 17.0)
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            rothfusz + (rh - 85.0) / 10.0 * (87.0 - t) / 5.0
+        } else {
+            rothfusz
+        }
+    };
+    (index - 32.0) * 5.0 / 9.0
+}
+
+// Everything above for one reading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Psychrometrics {
+    pub dew_point: f64,  // °C
+    pub absolute_humidity: f64,  // g/m³
+    pub vapour_pressure_deficit: f64,  // kPa
+    pub humidex: f64,  // °C equivalent
+    pub heat_index: f64  // °C
+}
+
+impl Psychrometrics {
+    pub fn new(temperature: f64, humidity: f64) -> Psychrometrics {
+        Psychrometrics {
+            dew_point: dew_point(temperature, humidity),
+            absolute_humidity: absolute_humidity(temperature, humidity),
+            vapour_pressure_deficit: vapour_pressure_deficit(temperature, humidity),
+            humidex: humidex(temperature, humidity),
+            heat_index: heat_index(temperature, humidity)
+        }
+    }
+
+    // None when temperature or humidity was skipped, or on a BMP280
+    pub fn from_measurement(measurement: &BME280Measurement) -> Option<Psychrometrics> {
+        Some(Psychrometrics::new(measurement.temperature?, measurement.humidity?))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f64, expected: f64, tolerance: f64) {
+        assert!(fabs(value - expected) <= tolerance, "{value} is not within {tolerance} of {expected}");
+    }
+
+    #[test]
+    fn saturation_vapour_pressure_table() {
+        // Buck (1981) table, hPa
+        assert_close(saturation_vapour_pressure(0.0), 6.1121, 1e-4);
+        assert_close(saturation_vapour_pressure(20.0), 23.388, 0.01);
+        assert_close(saturation_vapour_pressure(30.0), 42.467, 0.02);
+        assert_close(saturation_vapour_pressure(-10.0), 2.8652, 0.002);
+    }
+
+    #[test]
+    fn dew_point_table() {
+        assert_close(dew_point(20.0, 100.0), 20.0, 1e-9);
+        assert_close(dew_point(25.0, 50.0), 13.9, 0.1);
+        assert_close(dew_point(30.0, 70.0), 23.9, 0.1);
+        assert_close(dew_point(10.0, 30.0), -6.8, 0.1);
+    }
+
+    #[test]
+    fn absolute_humidity_and_deficit() {
+        // Saturated air holds 17.3 g/m³ at 20 °C and 30.4 g/m³ at 30 °C
+        assert_close(absolute_humidity(20.0, 100.0), 17.3, 0.05);
+        assert_close(absolute_humidity(30.0, 100.0), 30.4, 0.1);
+        assert_close(vapour_pressure_deficit(25.0, 50.0), 1.58, 0.01);
+        assert_close(vapour_pressure_deficit(20.0, 100.0), 0.0, 1e-12);
+    }
+
+    #[test]
+    fn humidex_table() {
+        // Environment Canada humidex table
+        assert_close(humidex(30.0, 70.0), 41.0, 0.5);
+        assert_close(humidex(35.0, 50.0), 45.0, 0.5);
+    }
+
+    #[test]
+    fn heat_index_table() {
+        // NWS heat index chart: 90 °F at 70% is 106 °F, 96 °F at 65% is 121 °F
+        assert_close(heat_index(32.22, 70.0), 41.1, 0.3);
+        assert_close(heat_index(35.56, 65.0), 49.4, 0.3);
+        // Below 80 °F the simple formula stays close to the air temperature
+        assert_close(heat_index(20.0, 50.0), 19.4, 0.1);
+    }
+
+    #[test]
+    fn from_measurement() {
+        let measurement = BME280Measurement { temperature: Some(25.0), pressure: Some(101325.0), humidity: Some(50.0) };
+        let psychrometrics = Psychrometrics::from_measurement(&measurement).unwrap();
+        assert_close(psychrometrics.dew_point, 13.85, 0.01);
+
+        let skipped = BME280Measurement { humidity: None, ..measurement };
+        assert_eq!(Psychrometrics::from_measurement(&skipped), None);
+    }
+}
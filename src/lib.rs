@@ -2,9 +2,27 @@
 // https://github.com/sparkfun/Qwiic_BME280_Py
 // and from the Bosch BME280 manual.
 
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
 mod sensors;
 mod protocols;
+mod measurement;
+mod atmosphere;
 
 pub use sensors::bme280;
+pub use sensors::bme680;
 pub use sensors::veml6030;
-pub use sensors::moisture;
\ No newline at end of file
+pub use sensors::moisture;
+pub use atmosphere::psychrometrics;
+pub use atmosphere::altitude;
+pub use atmosphere::forecast;
+pub use protocols::bus;
+pub use protocols::gpio;
+pub use sensors::SensorError;
+#[cfg(feature = "std")]
+pub use sensors::Sensor;
+pub use measurement::{Quantity, SensorId, Unit};
+#[cfg(feature = "std")]
+pub use measurement::Measurement;
+#[cfg(feature = "embedded-hal-02")]
+pub use protocols::i2c::{LegacyI2C, LegacyI2CError};
@@ -1,29 +1,39 @@
 extern crate chrono;
 
 use linux_embedded_hal::I2cdev;
+use linux_embedded_hal::i2cdev::linux::LinuxI2CError;
 
 use chrono::offset::Utc;
 use chrono::DateTime;
 
 use std::{thread, time, time::SystemTime};
 
-use hello_i2c::{bme280, moisture, veml6030};
+use hello_i2c::{bme280, bus::MutexBus, moisture, veml6030, LegacyI2C, LegacyI2CError, Sensor};
 
 fn main() {
-    let mut sensor_bme280 = bme280::BME280::build(
-        I2cdev::new("/dev/i2c-1").unwrap(),
+    // One adapter, shared by every sensor on the bus
+    let bus = MutexBus::new(LegacyI2C(I2cdev::new("/dev/i2c-1").unwrap()));
+
+    let sensor_bme280 = bme280::BME280::build(
+        bus.acquire(),
         bme280::Address::Alternative.into()
-    );
+    ).unwrap();
 
-    let mut sensor_moisture = moisture::Moisture::build(
-        I2cdev::new("/dev/i2c-1").unwrap(),
+    let sensor_moisture = moisture::Moisture::build(
+        bus.acquire(),
         moisture::Address::Default.into()
     );
 
-    let mut sensor_veml6030 = veml6030::VEML6030::build(
-        I2cdev::new("/dev/i2c-1").unwrap(),
+    let sensor_veml6030 = veml6030::VEML6030::build(
+        bus.acquire(),
         veml6030::Address::Default.into()
-    );
+    ).unwrap();
+
+    let mut sensors: Vec<Box<dyn Sensor<BusError = LegacyI2CError<LinuxI2CError>> + '_>> = vec![
+        Box::new(sensor_bme280),
+        Box::new(sensor_moisture),
+        Box::new(sensor_veml6030),
+    ];
 
     thread::sleep(time::Duration::from_secs(1));
 
@@ -32,19 +42,16 @@ fn main() {
         let timestamp: DateTime<Utc> = timestamp.into();
         let timestamp = timestamp.format("%Y-%m-%dT%T");
 
-        let temp = sensor_bme280.get_temperature_celsius().unwrap();
-        let hum = sensor_bme280.get_humidity_relative().unwrap();
-        let press = sensor_bme280.get_pressure_pascal().unwrap();
-
-        println!("[{timestamp}] temperature={temp}, humididy={hum}, pressure={press}");
-
-        let moisture_level = sensor_moisture.get_moisture_level().unwrap();
-
-        println!("[{timestamp}] moisture_level={moisture_level}");
-
-        let lux = sensor_veml6030.get_ambient_light_lux().unwrap();
-
-        println!("[{timestamp}] lux={lux}");
+        for sensor in sensors.iter_mut() {
+            match sensor.sample() {
+                Ok(measurements) => {
+                    for measurement in measurements {
+                        println!("[{timestamp}] {measurement}");
+                    }
+                },
+                Err(error) => eprintln!("[{timestamp}] {} failed: {error:?}", sensor.id())
+            }
+        }
 
         thread::sleep(time::Duration::from_secs(3));
     }
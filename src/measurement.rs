@@ -0,0 +1,126 @@
+use core::fmt;
+
+#[cfg(feature = "std")]
+use chrono::{DateTime, Utc};
+
+// Physical quantity reported by a sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantity {
+    Temperature,
+    Pressure,
+    RelativeHumidity,
+    AmbientLight,
+    Moisture,
+    GasResistance
+}
+
+impl Quantity {
+    // Unit the drivers report this quantity in.
+    pub fn unit(&self) -> Unit {
+        match self {
+            Quantity::Temperature => Unit::Celsius,
+            Quantity::Pressure => Unit::Pascal,
+            Quantity::RelativeHumidity => Unit::Percent,
+            Quantity::AmbientLight => Unit::Lux,
+            Quantity::Moisture => Unit::Counts,
+            Quantity::GasResistance => Unit::Ohm
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Quantity::Temperature => "temperature",
+            Quantity::Pressure => "pressure",
+            Quantity::RelativeHumidity => "humidity",
+            Quantity::AmbientLight => "ambient_light",
+            Quantity::Moisture => "moisture",
+            Quantity::GasResistance => "gas_resistance"
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Celsius,
+    Pascal,
+    Percent,
+    Lux,
+    Counts,  // Raw, uncalibrated ADC counts
+    Ohm
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Unit::Celsius => "°C",
+            Unit::Pascal => "Pa",
+            Unit::Percent => "%",
+            Unit::Lux => "lx",
+            Unit::Counts => "counts",
+            Unit::Ohm => "Ω"
+        };
+        write!(f, "{symbol}")
+    }
+}
+
+// Identifies a physical device: the driver model and its bus address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SensorId {
+    pub model: &'static str,
+    pub address: u8
+}
+
+impl SensorId {
+    pub fn new(model: &'static str, address: u8) -> SensorId {
+        SensorId { model, address }
+    }
+}
+
+impl fmt::Display for SensorId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{:#04x}", self.model, self.address)
+    }
+}
+
+// Timestamped reading, only available with `std` for the clock
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub quantity: Quantity,
+    pub value: f64,
+    pub unit: Unit,
+    pub timestamp: DateTime<Utc>,
+    pub sensor_id: SensorId
+}
+
+#[cfg(feature = "std")]
+impl Measurement {
+    pub fn new(sensor_id: SensorId, quantity: Quantity, value: f64, timestamp: DateTime<Utc>) -> Measurement {
+        Measurement { quantity, value, unit: quantity.unit(), timestamp, sensor_id }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}={} {}", self.sensor_id, self.quantity, self.value, self.unit)
+    }
+}
+
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_measurement() {
+        let sensor_id = SensorId::new("bme280", 0x76);
+        let measurement = Measurement::new(sensor_id, Quantity::Pressure, 101325.0, Utc::now());
+
+        assert_eq!(measurement.unit, Unit::Pascal);
+        assert_eq!(measurement.to_string(), "bme280@0x76 pressure=101325 Pa");
+    }
+}
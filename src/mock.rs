@@ -1,154 +1,77 @@
 extern crate chrono;
 
-use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
 
 use hello_i2c::{bme280, moisture, veml6030};
 
-fn mock_veml6030() -> veml6030::VEML6030<I2cMock> {
+fn mock_veml6030() -> (veml6030::VEML6030<I2cMock>, I2cMock) {
     let address: u8 = veml6030::Address::Default.into();
     let expectations = [
+        // Setting register read once, then written from the driver's shadow copy
         I2cTransaction::write_read(address, vec![0x00], vec![0x00, 0x00]),
         I2cTransaction::write(address, vec![0x00, 0x00, 0x00]),
-        I2cTransaction::write_read(address, vec![0x00], vec![0x00, 0x00]),
         I2cTransaction::write(address, vec![0x00, 0x18, 0x00]),
-        I2cTransaction::write_read(address, vec![0x00], vec![0x18, 0x00]),
         I2cTransaction::write(address, vec![0x00, 0x1A, 0x00]),
+        // Lux reading, converted with the shadowed gain and integration time
         I2cTransaction::write_read(address, vec![0x04], vec![0x00, 0xFF]),
-        I2cTransaction::write_read(address, vec![0x00], vec![0x18, 0x00]),
-        I2cTransaction::write_read(address, vec![0x00], vec![0x1A, 0x00]),
     ];
     let i2c = I2cMock::new(&expectations);
 
-    veml6030::VEML6030::build(i2c, address)
+    (veml6030::VEML6030::build(i2c.clone(), address).unwrap(), i2c)
 }
 
-fn mock_bme280() -> bme280::BME280<I2cMock> {
+// Calibration registers 0x88..0xA1 and 0xE1..0xE7, little endian
+const BME280_TP_BLOCK: [u8; 26] = [
+    0x45, 0x6F, 0x6F, 0x68, 0x32, 0x00,  // T1 28485, T2 26735, T3 50
+    0x82, 0x8F, 0x75, 0xD6, 0xD0, 0x0B, 0x44, 0x1B, 0xFC, 0xFF,  // P1 36738, P2 -10635, P3 3024, P4 6980, P5 -4
+    0xF9, 0xFF, 0xAC, 0x26, 0x0A, 0xD8, 0xBD, 0x10,  // P6 -7, P7 9900, P8 -10230, P9 4285
+    0x00, 0x4B,  // Unused, H1 75
+];
+// H2 365, H3 0, H4 312, H5 50, H6 30
+const BME280_H_BLOCK: [u8; 7] = [0x6D, 0x01, 0x00, 0x13, 0x28, 0x03, 0x1E];
+// Data registers 0xF7..0xFE: pressure, temperature, humidity
+const BME280_DATA_BLOCK: [u8; 8] = [82, 79, 0, 128, 189, 0, 117, 97];
+
+fn mock_bme280() -> (bme280::BME280<I2cMock>, I2cMock) {
     let address: u8 = bme280::Address::Default.into();
     let expectations = [
-        // Temperature calibration
-        // T1 calibration
-        I2cTransaction::write_read(address, vec![0x88], ((28485_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x89], ((28485_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // T2 calibration
-        I2cTransaction::write_read(address, vec![0x8A], ((26735_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x8B], ((26735_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // T3 calibration
-        I2cTransaction::write_read(address, vec![0x8C], ((50_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x8D], ((50_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-
-        // Pressure calibration
-        // P1 calibration
-        I2cTransaction::write_read(address, vec![0x8E], ((36738_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x8F], ((36738_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // P2 calibration
-        I2cTransaction::write_read(address, vec![0x90], ((-10635_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x91], ((-10635_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // P3 calibration
-        I2cTransaction::write_read(address, vec![0x92], ((3024_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x93], ((3024_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // P4 calibration
-        I2cTransaction::write_read(address, vec![0x94], ((6980_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x95], ((6980_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // P5 calibration
-        I2cTransaction::write_read(address, vec![0x96], ((-4_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x97], ((-4_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // P6 calibration
-        I2cTransaction::write_read(address, vec![0x98], ((-7_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x99], ((-7_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // P7 calibration
-        I2cTransaction::write_read(address, vec![0x9A], ((9900_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x9B], ((9900_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // P8 calibration
-        I2cTransaction::write_read(address, vec![0x9C], ((-10230_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x9D], ((-10230_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // P9 calibration
-        I2cTransaction::write_read(address, vec![0x9E], ((4285_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0x9F], ((4285_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-
-        // Humidity calibration
-        // H1 calibration
-        I2cTransaction::write_read(address, vec![0xA1], ((75_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        // H2 calibration
-        I2cTransaction::write_read(address, vec![0xE1], ((365_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0xE2], ((365_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // H3 calibration
-        I2cTransaction::write_read(address, vec![0xE3], ((0_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        // H4 calibration
-        I2cTransaction::write_read(address, vec![0xE4], ((312_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0xE5], ((312_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // H5 calibration
-        I2cTransaction::write_read(address, vec![0xE6], ((50_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-        I2cTransaction::write_read(address, vec![0xE5], ((50_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-        // H6 calibration
-        I2cTransaction::write_read(address, vec![0xE7], ((30_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-
-        // Standby
-        I2cTransaction::write_read(address, vec![0xF5], vec![0x00]),  // Get stanby status
-        I2cTransaction::write(address, vec![0xF5, 0x00]),  // Set standy time
-        // Filter
-        I2cTransaction::write_read(address, vec![0xF5], vec![0x00]),  // Get filter status
-        I2cTransaction::write(address, vec![0xF5, 0x00]),  // Set filter
-
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read current Mode
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read current Mode
-        I2cTransaction::write(address, vec![0xF4, 0x00]),  // Write Mode sleep
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read temperature oversample
-        I2cTransaction::write(address, vec![0xF4, 0x01 << 5]),  // Write temperature oversample 0x1
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read current Mode
-        I2cTransaction::write(address, vec![0xF4, 0x00]),  // Write Mode sleep
-
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read current Mode
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read current Mode
-        I2cTransaction::write(address, vec![0xF4, 0x00]),  // Write Mode sleep
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read pressure oversample
-        I2cTransaction::write(address, vec![0xF4, 0x01 << 2]),  // Write pressure oversample 0x1
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read current Mode
-        I2cTransaction::write(address, vec![0xF4, 0x00]),  // Write Mode sleep
-
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read current Mode
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read current Mode
-        I2cTransaction::write(address, vec![0xF4, 0x00]),  // Write Mode sleep
-        I2cTransaction::write_read(address, vec![0xF2], vec![0x00]),  // Read humidity oversample
+        // Chip ID, then calibration, one burst per block
+        I2cTransaction::write_read(address, vec![0xD0], vec![0x60]),
+        I2cTransaction::write_read(address, vec![0x88], BME280_TP_BLOCK.to_vec()),
+        I2cTransaction::write_read(address, vec![0xE1], BME280_H_BLOCK.to_vec()),
+
+        // Default configuration
+        I2cTransaction::write(address, vec![0xF4, 0x24]),  // Sleep, temperature and pressure oversample 0x1
+        I2cTransaction::write_read(address, vec![0xF2], vec![0x00]),  // Read humidity control
         I2cTransaction::write(address, vec![0xF2, 0x01]),  // Write humidity oversample 0x1
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read current Mode
-        I2cTransaction::write(address, vec![0xF4, 0x00]),  // Write Mode sleep
-
-        I2cTransaction::write_read(address, vec![0xF4], vec![0x00]),  // Read current Mode
-        I2cTransaction::write(address, vec![0xF4, 0x03]),  // Write Mode normal
-
-        // Read temperature
-        I2cTransaction::write_read(address, vec![0xFA], vec![0x00]),
-        I2cTransaction::write_read(address, vec![0xFB], vec![0x00]),
-        I2cTransaction::write_read(address, vec![0xFC], vec![0x00]),
-        // Read pressure
-        I2cTransaction::write_read(address, vec![0xF7], vec![0x00]),
-        I2cTransaction::write_read(address, vec![0xF8], vec![0x00]),
-        I2cTransaction::write_read(address, vec![0xF9], vec![0x00]),
-        // Read humidity
-        I2cTransaction::write_read(address, vec![0xFD], vec![115]),
-        I2cTransaction::write_read(address, vec![0xFE], vec![249]),
+        I2cTransaction::write_read(address, vec![0xF5], vec![0x00]),  // Read config
+        I2cTransaction::write(address, vec![0xF5, 0x00]),  // Write standby time and filter
+        I2cTransaction::write(address, vec![0xF4, 0x27]),  // Write Mode normal
+
+        // Read temperature, pressure and humidity in one data burst
+        I2cTransaction::write_read(address, vec![0xF7], BME280_DATA_BLOCK.to_vec()),
     ];
     let i2c = I2cMock::new(&expectations);
 
-    bme280::BME280::build(i2c, address)
+    (bme280::BME280::build(i2c.clone(), address).unwrap(), i2c)
 }
 
-fn mock_moisture() -> moisture::Moisture<I2cMock> {
+fn mock_moisture() -> (moisture::Moisture<I2cMock>, I2cMock) {
     let address: u8 = moisture::Address::Default.into();
     let expectations = [
         I2cTransaction::write_read(address, vec![0x05], vec![0x00, 0x00]),
     ];
     let i2c = I2cMock::new(&expectations);
 
-    moisture::Moisture::build(i2c, address)
+    (moisture::Moisture::build(i2c.clone(), address), i2c)
 }
 
 
 
 fn main() {
-    let mut sensor_veml6030 = mock_veml6030();
-    let mut sensor_bme280 = mock_bme280();
-    let mut sensor_moisture = mock_moisture();
+    let (mut sensor_veml6030, mut i2c_veml6030) = mock_veml6030();
+    let (mut sensor_bme280, mut i2c_bme280) = mock_bme280();
+    let (mut sensor_moisture, mut i2c_moisture) = mock_moisture();
     
     // Ambient light sensor
     let value = sensor_veml6030.get_ambient_light_lux().unwrap();
@@ -159,10 +82,12 @@ fn main() {
     println!("Moisture level: {}", value);
 
     // Atmospheric sensor
-    let value = sensor_bme280.get_temperature_celsius().unwrap();
-    println!("Temperature value: {}", value);
-    let value = sensor_bme280.get_pressure_pascal().unwrap();
-    println!("Pressure value: {}", value);
-    let value = sensor_bme280.get_humidity_relative().unwrap();
-    println!("Humidity value: {}", value);
+    let measurement = sensor_bme280.measure().unwrap();
+    println!("Temperature value: {:?}", measurement.temperature);
+    println!("Pressure value: {:?}", measurement.pressure);
+    println!("Humidity value: {:?}", measurement.humidity);
+
+    i2c_veml6030.done();
+    i2c_bme280.done();
+    i2c_moisture.done();
 }
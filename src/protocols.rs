@@ -1 +1,3 @@
+pub mod bus;
+pub mod gpio;
 pub mod i2c;
\ No newline at end of file
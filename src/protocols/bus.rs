@@ -0,0 +1,130 @@
+// Shares one I2C adapter between several drivers. Each bus hands out
+// per-device handles implementing `I2c`, and every transaction takes
+// exclusive access to the adapter, so drivers run unchanged on top.
+use core::cell::RefCell;
+
+use embedded_hal::i2c::I2c;
+use embedded_hal_bus::i2c::{CriticalSectionDevice, RefCellDevice};
+#[cfg(feature = "std")]
+use embedded_hal_bus::i2c::MutexDevice;
+
+// Single-threaded sharing, the cheapest option when all drivers live in one
+// thread or task.
+pub struct RefCellBus<I2C> {
+    bus: RefCell<I2C>
+}
+
+impl<I2C: I2c> RefCellBus<I2C> {
+    pub fn new(i2c: I2C) -> RefCellBus<I2C> {
+        RefCellBus { bus: RefCell::new(i2c) }
+    }
+
+    pub fn acquire(&self) -> RefCellDevice<'_, I2C> {
+        RefCellDevice::new(&self.bus)
+    }
+
+    pub fn into_inner(self) -> I2C {
+        self.bus.into_inner()
+    }
+}
+
+// Sharing across interrupt priorities on bare metal. Transactions run inside
+// a critical section, so they add to interrupt latency.
+pub struct CriticalSectionBus<I2C> {
+    bus: critical_section::Mutex<RefCell<I2C>>
+}
+
+impl<I2C: I2c> CriticalSectionBus<I2C> {
+    pub const fn new(i2c: I2C) -> CriticalSectionBus<I2C> {
+        CriticalSectionBus { bus: critical_section::Mutex::new(RefCell::new(i2c)) }
+    }
+
+    pub fn acquire(&self) -> CriticalSectionDevice<'_, I2C> {
+        CriticalSectionDevice::new(&self.bus)
+    }
+
+    pub fn into_inner(self) -> I2C {
+        self.bus.into_inner().into_inner()
+    }
+}
+
+// Sharing across threads. A poisoned lock panics on the next transaction.
+#[cfg(feature = "std")]
+pub struct MutexBus<I2C> {
+    bus: std::sync::Mutex<I2C>
+}
+
+#[cfg(feature = "std")]
+impl<I2C: I2c> MutexBus<I2C> {
+    pub fn new(i2c: I2C) -> MutexBus<I2C> {
+        MutexBus { bus: std::sync::Mutex::new(i2c) }
+    }
+
+    pub fn acquire(&self) -> MutexDevice<'_, I2C> {
+        MutexDevice::new(&self.bus)
+    }
+
+    pub fn into_inner(self) -> I2C {
+        self.bus.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use crate::moisture::{self, Moisture};
+    use crate::veml6030::{self, VEML6030};
+
+    use super::*;
+
+    fn expectations() -> [I2cTransaction; 2] {
+        let moisture_address: u8 = moisture::Address::Default.into();
+        let veml6030_address: u8 = veml6030::Address::Default.into();
+        [
+            I2cTransaction::write_read(moisture_address, vec![0x05], vec![0x01, 0x2C]),
+            I2cTransaction::write_read(veml6030_address, vec![0x04], vec![0x00, 0x64]),
+        ]
+    }
+
+    #[test]
+    fn refcell_bus_shares_one_adapter() {
+        let bus = RefCellBus::new(I2cMock::new(&expectations()));
+
+        let mut moisture_sensor = Moisture::build(bus.acquire(), moisture::Address::Default.into());
+        let mut veml6030 = VEML6030::new(bus.acquire(), veml6030::Address::Default.into());
+
+        assert_eq!(moisture_sensor.get_moisture_level().unwrap(), 300);
+        assert_eq!(veml6030.get_ambient_light_output().unwrap(), 100);
+        bus.into_inner().done();
+    }
+
+    #[test]
+    fn critical_section_bus_shares_one_adapter() {
+        let bus = CriticalSectionBus::new(I2cMock::new(&expectations()));
+
+        let mut moisture_sensor = Moisture::build(bus.acquire(), moisture::Address::Default.into());
+        let mut veml6030 = VEML6030::new(bus.acquire(), veml6030::Address::Default.into());
+
+        assert_eq!(moisture_sensor.get_moisture_level().unwrap(), 300);
+        assert_eq!(veml6030.get_ambient_light_output().unwrap(), 100);
+        bus.into_inner().done();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn mutex_bus_shares_one_adapter_across_threads() {
+        let bus = MutexBus::new(I2cMock::new(&expectations()));
+
+        let mut moisture_sensor = Moisture::build(bus.acquire(), moisture::Address::Default.into());
+        let mut veml6030 = VEML6030::new(bus.acquire(), veml6030::Address::Default.into());
+
+        // The second thread only starts once the first transaction is done
+        std::thread::scope(|scope| {
+            scope.spawn(|| assert_eq!(moisture_sensor.get_moisture_level().unwrap(), 300)).join().unwrap();
+            scope.spawn(|| assert_eq!(veml6030.get_ambient_light_output().unwrap(), 100));
+        });
+        bus.into_inner().done();
+    }
+}
@@ -0,0 +1,51 @@
+// Input line a sensor's interrupt output is wired to. Drivers only need to
+// block until the line signals, so any edge or level source can implement it.
+pub trait InterruptPin {
+    type Error;
+
+    // Block until the sensor asserts its interrupt output
+    fn wait_for_interrupt(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<P: InterruptPin> InterruptPin for &mut P {
+    type Error = P::Error;
+
+    fn wait_for_interrupt(&mut self) -> Result<(), Self::Error> {
+        P::wait_for_interrupt(self)
+    }
+}
+
+#[cfg(feature = "gpio-cdev")]
+pub use cdev::CdevInterruptPin;
+
+#[cfg(feature = "gpio-cdev")]
+mod cdev {
+    use std::path::Path;
+
+    use gpio_cdev::{Chip, EventRequestFlags, LineEventHandle, LineRequestFlags};
+
+    use super::InterruptPin;
+
+    // Linux GPIO character device line, e.g. `/dev/gpiochip0` line 17. Open
+    // drain, active low outputs (VEML6030 INT) signal on the falling edge.
+    pub struct CdevInterruptPin {
+        events: LineEventHandle
+    }
+
+    impl CdevInterruptPin {
+        pub fn new<P: AsRef<Path>>(chip: P, line: u32) -> Result<CdevInterruptPin, gpio_cdev::Error> {
+            let events = Chip::new(chip)?
+                .get_line(line)?
+                .events(LineRequestFlags::INPUT, EventRequestFlags::FALLING_EDGE, "hello-i2c")?;
+            Ok(CdevInterruptPin { events })
+        }
+    }
+
+    impl InterruptPin for CdevInterruptPin {
+        type Error = gpio_cdev::Error;
+
+        fn wait_for_interrupt(&mut self) -> Result<(), Self::Error> {
+            self.events.get_event().map(|_| ())
+        }
+    }
+}
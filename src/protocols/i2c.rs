@@ -1,40 +1,153 @@
-use embedded_hal::blocking::i2c;
+use embedded_hal::i2c::I2c;
+
+// Longest register write (register address included) the drivers issue
+const MAX_WRITE_LEN: usize = 8;
 
 pub struct I2CWrapper<I2C> {
     i2c: I2C,
     address: u8
 }
 
+// Bus failure, carrying the error reported by the HAL.
 #[derive(Debug)]
-pub enum I2CError{
-    IOError
+pub enum I2CError<E> {
+    IOError(E),
+    WriteTooLong(usize)  // Payload length, nothing was sent
 }
 
-impl<I2C: i2c::Write + i2c::WriteRead> I2CWrapper<I2C> {
+impl<I2C: I2c> I2CWrapper<I2C> {
     pub fn new(i2c: I2C, address: u8) -> I2CWrapper<I2C> {
         I2CWrapper { address, i2c }
     }
 
-    pub fn read_from_register(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), I2CError> {
-        match self.i2c.write_read(self.address, &[register], buffer) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(I2CError::IOError)
-        }
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    pub fn read_from_register(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), I2CError<I2C::Error>> {
+        self.i2c.write_read(self.address, &[register], buffer)
+            .map_err(I2CError::IOError)
+    }
+
+    pub fn write_to_register(&mut self, register: u8, bytes: &[u8]) -> Result<(), I2CError<I2C::Error>> {
+        let mut buffer = [0u8; MAX_WRITE_LEN];
+        let buffer = register_write(&mut buffer, register, bytes)?;
+        self.i2c.write(self.address, buffer)
+            .map_err(I2CError::IOError)
     }
 
-    pub fn write_to_register(&mut self, register: u8, bytes: &[u8]) -> Result<(), I2CError> {
-        let mut buffer = Vec::<u8>::with_capacity(1+bytes.len());
-        buffer.push(register);
-        for value in bytes {
-            buffer.push(*value);
+}
+
+// Lay out the register address followed by the payload in `buffer`.
+fn register_write<'a, E>(buffer: &'a mut [u8; MAX_WRITE_LEN], register: u8, bytes: &[u8]) -> Result<&'a [u8], I2CError<E>> {
+    if bytes.len() >= MAX_WRITE_LEN {
+        return Err(I2CError::WriteTooLong(bytes.len()))
+    }
+    buffer[0] = register;
+    buffer[1..=bytes.len()].copy_from_slice(bytes);
+    Ok(&buffer[..=bytes.len()])
+}
+
+#[cfg(feature = "async")]
+pub use asynch::AsyncI2CWrapper;
+
+#[cfg(feature = "async")]
+mod asynch {
+    use embedded_hal_async::i2c::I2c;
+
+    use super::{register_write, I2CError, MAX_WRITE_LEN};
+
+    pub struct AsyncI2CWrapper<I2C> {
+        i2c: I2C,
+        address: u8
+    }
+
+    impl<I2C: I2c> AsyncI2CWrapper<I2C> {
+        pub fn new(i2c: I2C, address: u8) -> AsyncI2CWrapper<I2C> {
+            AsyncI2CWrapper { address, i2c }
         }
-        // TODO check if it matches write_bytes
-        match self.i2c.write(self.address, &buffer) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(I2CError::IOError)
+
+        pub fn address(&self) -> u8 {
+            self.address
+        }
+
+        pub async fn read_from_register(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), I2CError<I2C::Error>> {
+            self.i2c.write_read(self.address, &[register], buffer).await
+                .map_err(I2CError::IOError)
+        }
+
+        pub async fn write_to_register(&mut self, register: u8, bytes: &[u8]) -> Result<(), I2CError<I2C::Error>> {
+            let mut buffer = [0u8; MAX_WRITE_LEN];
+            let buffer = register_write(&mut buffer, register, bytes)?;
+            self.i2c.write(self.address, buffer).await
+                .map_err(I2CError::IOError)
         }
     }
+}
+
+#[cfg(feature = "embedded-hal-02")]
+pub use legacy::{LegacyI2C, LegacyI2CError};
+
+// Lets devices implementing the embedded-hal 0.2 blocking traits drive the
+// embedded-hal 1.0 based sensors, e.g. `LegacyI2C(I2cdev::new(...)?)`.
+#[cfg(feature = "embedded-hal-02")]
+mod legacy {
+    use embedded_hal::i2c::{self, ErrorKind, ErrorType, I2c, Operation};
+    use embedded_hal_02::blocking::i2c::{Read, Write, WriteRead};
+
+    pub struct LegacyI2C<I2C>(pub I2C);
+
+    #[derive(Debug)]
+    pub struct LegacyI2CError<E>(pub E);
+
+    impl<E: core::fmt::Debug> i2c::Error for LegacyI2CError<E> {
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    impl<I2C, E> ErrorType for LegacyI2C<I2C>
+    where
+        I2C: Read<Error = E> + Write<Error = E> + WriteRead<Error = E>,
+        E: core::fmt::Debug
+    {
+        type Error = LegacyI2CError<E>;
+    }
 
+    impl<I2C, E> I2c for LegacyI2C<I2C>
+    where
+        I2C: Read<Error = E> + Write<Error = E> + WriteRead<Error = E>,
+        E: core::fmt::Debug
+    {
+        fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+            self.0.read(address, read).map_err(LegacyI2CError)
+        }
+
+        fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+            self.0.write(address, write).map_err(LegacyI2CError)
+        }
+
+        fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+            self.0.write_read(address, write, read).map_err(LegacyI2CError)
+        }
+
+        // 0.2 has no generic transaction, a write followed by a read keeps the
+        // repeated start, anything else is issued one operation at a time.
+        fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+            match operations {
+                [Operation::Write(write), Operation::Read(read)] => self.write_read(address, write, read),
+                _ => {
+                    for operation in operations {
+                        match operation {
+                            Operation::Write(write) => self.write(address, write)?,
+                            Operation::Read(read) => self.read(address, read)?,
+                        }
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
 }
 
 
@@ -45,12 +158,12 @@ mod tests {
     use super::*;
 
     use byteorder::{ByteOrder, BigEndian};
-    use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use embedded_hal::i2c::ErrorKind;
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
 
-    fn prepare_mock_device(expectations: &[I2cTransaction]) -> I2CWrapper<I2cMock> {
+    fn prepare_mock_device(expectations: &[I2cTransaction]) -> (I2CWrapper<I2cMock>, I2cMock) {
         let i2c = I2cMock::new(expectations);
-        I2CWrapper::new(i2c, 0x00)
-
+        (I2CWrapper::new(i2c.clone(), 0x00), i2c)
     }
     
     #[test]
@@ -65,7 +178,7 @@ mod tests {
             I2cTransaction::write_read(ADDRESS, vec![REGISTER], mock_buffer.clone())
         ];
 
-        let mut wrapper = prepare_mock_device(&expectations);
+        let (mut wrapper, mut i2c) = prepare_mock_device(&expectations);
 
         let mut read_buffer = vec![0u8, 0u8];
         let result = wrapper.read_from_register(0u8, &mut read_buffer);
@@ -77,6 +190,7 @@ mod tests {
         for x in zip(read_buffer, mock_buffer) {
             assert_eq!(x.0, x.1)
         }
+        i2c.done();
     }
 
     #[test]
@@ -98,11 +212,89 @@ mod tests {
             )
         ];
 
-        let mut wrapper = prepare_mock_device(&expectations);
+        let (mut wrapper, mut i2c) = prepare_mock_device(&expectations);
 
         let result = wrapper.write_to_register(0u8, &mock_buffer);
         
         // Check result
         assert_eq!(result.unwrap(), ());
+        i2c.done();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn oversized_write_is_rejected() {
+        let (mut wrapper, mut i2c) = prepare_mock_device(&[]);
+
+        let result = wrapper.write_to_register(0u8, &[0u8; MAX_WRITE_LEN]);
+
+        assert!(matches!(result, Err(I2CError::WriteTooLong(MAX_WRITE_LEN))));
+        i2c.done();
+    }
+
+    #[test]
+    fn bus_error_is_propagated() {
+        const ADDRESS: u8 = 0x00;
+        const REGISTER: u8 = 0u8;
+
+        let expectations = [
+            I2cTransaction::write_read(ADDRESS, vec![REGISTER], vec![0u8])
+                .with_error(ErrorKind::Other)
+        ];
+
+        let (mut wrapper, mut i2c) = prepare_mock_device(&expectations);
+
+        let mut read_buffer = [0u8];
+        let result = wrapper.read_from_register(REGISTER, &mut read_buffer);
+
+        assert!(matches!(result, Err(I2CError::IOError(ErrorKind::Other))));
+        i2c.done();
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_register_access() {
+        use embassy_futures::block_on;
+
+        const ADDRESS: u8 = 0x00;
+        const REGISTER: u8 = 0u8;
+
+        let expectations = [
+            I2cTransaction::write_read(ADDRESS, vec![REGISTER], vec![0x12]),
+            I2cTransaction::write(ADDRESS, vec![REGISTER, 0x34]),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut wrapper = AsyncI2CWrapper::new(i2c.clone(), ADDRESS);
+
+        let mut read_buffer = [0u8];
+        block_on(wrapper.read_from_register(REGISTER, &mut read_buffer)).unwrap();
+        block_on(wrapper.write_to_register(REGISTER, &[0x34])).unwrap();
+
+        assert_eq!(read_buffer, [0x12]);
+        i2c.done();
+    }
+
+    #[cfg(feature = "embedded-hal-02")]
+    #[test]
+    fn legacy_i2c_register_access() {
+        use embedded_hal_mock::eh0::i2c::{Mock as LegacyMock, Transaction as LegacyTransaction};
+
+        const ADDRESS: u8 = 0x00;
+        const REGISTER: u8 = 0u8;
+
+        let expectations = [
+            LegacyTransaction::write_read(ADDRESS, vec![REGISTER], vec![0x12]),
+            LegacyTransaction::write(ADDRESS, vec![REGISTER, 0x34]),
+        ];
+        let mut i2c = LegacyMock::new(&expectations);
+
+        let mut wrapper = I2CWrapper::new(LegacyI2C(i2c.clone()), ADDRESS);
+
+        let mut read_buffer = [0u8];
+        wrapper.read_from_register(REGISTER, &mut read_buffer).unwrap();
+        wrapper.write_to_register(REGISTER, &[0x34]).unwrap();
+
+        assert_eq!(read_buffer, [0x12]);
+        i2c.done();
+    }
+}
@@ -1,3 +1,51 @@
 pub mod moisture;
 pub mod bme280;
-pub mod veml6030;
\ No newline at end of file
+pub mod bme680;
+pub mod veml6030;
+
+#[cfg(feature = "std")]
+use crate::measurement::{Measurement, SensorId};
+
+// Common interface so services can poll a heterogeneous list of sensors.
+// Sensors sharing a bus share its error type, so they fit in one list.
+#[cfg(feature = "std")]
+pub trait Sensor {
+    type BusError;
+
+    fn id(&self) -> SensorId;
+
+    // Read every quantity the sensor provides, sharing one timestamp.
+    fn sample(&mut self) -> Result<Vec<Measurement>, SensorError<Self::BusError>>;
+}
+
+#[derive(Debug)]
+pub enum SensorError<E> {
+    BME280(bme280::BME280Error<E>),
+    BME680(bme680::BME680Error<E>),
+    VEML6030(veml6030::VEML6030Error<E>),
+    Moisture(moisture::MoistureError<E>)
+}
+
+impl<E> From<bme280::BME280Error<E>> for SensorError<E> {
+    fn from(error: bme280::BME280Error<E>) -> Self {
+        SensorError::BME280(error)
+    }
+}
+
+impl<E> From<bme680::BME680Error<E>> for SensorError<E> {
+    fn from(error: bme680::BME680Error<E>) -> Self {
+        SensorError::BME680(error)
+    }
+}
+
+impl<E> From<veml6030::VEML6030Error<E>> for SensorError<E> {
+    fn from(error: veml6030::VEML6030Error<E>) -> Self {
+        SensorError::VEML6030(error)
+    }
+}
+
+impl<E> From<moisture::MoistureError<E>> for SensorError<E> {
+    fn from(error: moisture::MoistureError<E>) -> Self {
+        SensorError::Moisture(error)
+    }
+}
@@ -1,29 +1,70 @@
-use std::u8;
-
-use embedded_hal::blocking::i2c::{Write, WriteRead};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
 
 pub mod calibration;
 pub mod i2c;
 pub mod constants;
+pub mod config;
+pub mod typestate;
+pub(crate) mod fields;
+#[cfg(feature = "async")]
+mod asynch;
+
+#[cfg(feature = "async")]
+pub use asynch::BME280Async;
+pub use config::BME280Config;
+pub use fields::RawData;
 
 use constants::{values, addresses};
 
-use crate::protocols::i2c::I2CWrapper;
+#[cfg(feature = "std")]
+use chrono::Utc;
+
+#[cfg(feature = "std")]
+use crate::measurement::{Measurement, Quantity, SensorId};
+use crate::protocols::i2c::{I2CError, I2CWrapper};
+#[cfg(feature = "std")]
+use crate::sensors::{Sensor, SensorError};
+
+#[derive(Debug)]
+pub enum BME280Error<E> {
+    IOError(E),  // Bus failure, with the HAL's error
+    WriteTooLong(usize),  // Register write longer than the bus buffer
+    InvalidRegisterValue { register: u8, value: u8 },
+    ChipIdMismatch { expected: u8, found: u8 },
+    ConfigMismatch { expected: BME280Config, found: BME280Config },
+    ChannelSkipped(Channel),  // Channel disabled with `Oversampling::Skipped`
+    ChannelUnsupported(Channel),  // Channel the chip does not have
+    StandbyUnsupported(StandyTime),  // Standby time the chip does not have
+    NotReady  // Still copying NVM data or converting
+}
+
+impl<E> From<I2CError<E>> for BME280Error<E> {
+    fn from(error: I2CError<E>) -> Self {
+        match error {
+            I2CError::IOError(e) => BME280Error::IOError(e),
+            I2CError::WriteTooLong(len) => BME280Error::WriteTooLong(len)
+        }
+    }
+}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
     Sleep,
     Forced,
     Normal
 }
 
-impl From<u8> for Mode {
-    fn from(item: u8) -> Self {
+impl TryFrom<u8> for Mode {
+    type Error = u8;
+
+    fn try_from(item: u8) -> Result<Self, Self::Error> {
         match item {
-            0 => Self::Sleep,
-            1 => Self::Forced,
-            2 => Self::Forced,
-            3 => Self::Normal,
-            _ => panic!("Not expected")
+            0 => Ok(Self::Sleep),
+            1 => Ok(Self::Forced),
+            2 => Ok(Self::Forced),
+            3 => Ok(Self::Normal),
+            _ => Err(item)
         }
     }
 }
@@ -38,6 +79,7 @@ impl From<Mode> for u8 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Oversampling {
     Skipped,
     Ox1,
@@ -60,6 +102,20 @@ impl From<u8> for Oversampling {
     }
 }
 
+impl Oversampling {
+    // Number of samples averaged, 0 when the channel is skipped
+    pub fn factor(&self) -> u32 {
+        match self {
+            Oversampling::Skipped => 0,
+            Oversampling::Ox1 => 1,
+            Oversampling::Ox2 => 2,
+            Oversampling::Ox4 => 4,
+            Oversampling::Ox8 => 8,
+            Oversampling::Ox16 => 16,
+        }
+    }
+}
+
 impl From<Oversampling> for u8 {
     fn from(value: Oversampling) -> Self {
         match value {
@@ -73,6 +129,7 @@ impl From<Oversampling> for u8 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StandyTime {
     Ms0_5,
     Ms62_5,
@@ -80,41 +137,50 @@ pub enum StandyTime {
     Ms250,
     Ms500,
     Ms1000,
-    Ms10,
-    Ms20
+    Ms10,  // BME280 only
+    Ms20,  // BME280 only
+    Ms2000,  // BMP280 only
+    Ms4000  // BMP280 only
 }
 
-impl From<u8> for StandyTime {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => StandyTime::Ms0_5,
-            1 => StandyTime::Ms62_5,
-            2 => StandyTime::Ms125,
-            3 => StandyTime::Ms250,
-            4 => StandyTime::Ms500,
-            5 => StandyTime::Ms1000,
-            6 => StandyTime::Ms10,
-            7 => StandyTime::Ms20,
-            _ => panic!("Invalid standby value")
+// Codes 0b110 and 0b111 are 10 and 20 ms on the BME280 but 2000 and 4000 ms
+// on the BMP280, the rest are shared.
+impl StandyTime {
+    pub fn from_code(code: u8, variant: Variant) -> Result<StandyTime, u8> {
+        match (code, variant) {
+            (0, _) => Ok(StandyTime::Ms0_5),
+            (1, _) => Ok(StandyTime::Ms62_5),
+            (2, _) => Ok(StandyTime::Ms125),
+            (3, _) => Ok(StandyTime::Ms250),
+            (4, _) => Ok(StandyTime::Ms500),
+            (5, _) => Ok(StandyTime::Ms1000),
+            (6, Variant::BMP280) => Ok(StandyTime::Ms2000),
+            (7, Variant::BMP280) => Ok(StandyTime::Ms4000),
+            (6, _) => Ok(StandyTime::Ms10),
+            (7, _) => Ok(StandyTime::Ms20),
+            _ => Err(code)
         }
     }
-}
 
-impl From<StandyTime> for u8 {
-    fn from(value: StandyTime) -> Self {
-        match value {
-             StandyTime::Ms0_5 => 0,
-             StandyTime::Ms62_5 => 1,
-             StandyTime::Ms125 => 2,
-             StandyTime::Ms250 => 3,
-             StandyTime::Ms500 => 4,
-             StandyTime::Ms1000 => 5,
-             StandyTime::Ms10 => 6,
-             StandyTime::Ms20 => 7
+    // None when `variant` has no such standby time
+    pub fn code(self, variant: Variant) -> Option<u8> {
+        match (self, variant) {
+            (StandyTime::Ms0_5, _) => Some(0),
+            (StandyTime::Ms62_5, _) => Some(1),
+            (StandyTime::Ms125, _) => Some(2),
+            (StandyTime::Ms250, _) => Some(3),
+            (StandyTime::Ms500, _) => Some(4),
+            (StandyTime::Ms1000, _) => Some(5),
+            (StandyTime::Ms2000, Variant::BMP280) => Some(6),
+            (StandyTime::Ms4000, Variant::BMP280) => Some(7),
+            (StandyTime::Ms10, Variant::BME280) => Some(6),
+            (StandyTime::Ms20, Variant::BME280) => Some(7),
+            _ => None
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Filter {
     Off,
     C2,
@@ -148,6 +214,72 @@ impl From<Filter> for u8 {
     }
 }
 
+// Compensation formulas from the datasheet. Temperature and humidity use the
+// same 32 bit formulas in both integer backends, only pressure differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compensation {
+    Int32,  // 32 bit pressure, resolution of 1 Pa
+    #[default]
+    Int64,  // Bosch's recommended 64 bit pressure, Q24.8 Pa
+    Float  // Double precision formulas, no fixed point rounding
+}
+
+// Bosch sensors sharing this register layout, told apart by their chip ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    BMP280,  // Temperature and pressure only
+    BME280,
+    BME680  // Adds a gas sensor, driven by `bme680::BME680`
+}
+
+impl TryFrom<u8> for Variant {
+    type Error = u8;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            values::BMP280_CHIP_ID => Ok(Variant::BMP280),
+            values::CHIP_ID => Ok(Variant::BME280),
+            values::BME680_CHIP_ID => Ok(Variant::BME680),
+            _ => Err(id)
+        }
+    }
+}
+
+impl From<Variant> for u8 {
+    fn from(value: Variant) -> Self {
+        match value {
+            Variant::BMP280 => values::BMP280_CHIP_ID,
+            Variant::BME280 => values::CHIP_ID,
+            Variant::BME680 => values::BME680_CHIP_ID
+        }
+    }
+}
+
+impl Variant {
+    pub fn has_humidity(&self) -> bool {
+        *self != Variant::BMP280
+    }
+
+    pub fn has_gas(&self) -> bool {
+        *self == Variant::BME680
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::BMP280 => "bmp280",
+            Variant::BME280 => "bme280",
+            Variant::BME680 => "bme680"
+        }
+    }
+}
+
+// Read the chip ID to find out which sensor answers at `address`
+pub fn detect<I2C: I2c>(dev: &mut I2C, address: u8) -> Result<Variant, BME280Error<I2C::Error>> {
+    let id = i2c::read_id(&mut I2CWrapper::new(dev, address))?;
+    Variant::try_from(id).map_err(|found| BME280Error::ChipIdMismatch { expected: values::CHIP_ID, found })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Address {
     Default,
     Alternative
@@ -163,277 +295,927 @@ impl From<Address> for u8 {
 }
 
 
+// Maximum conversion time in µs (datasheet appendix B):
+// 1.25 + 2.3·T + (2.3·P + 0.575) + (2.3·H + 0.575) ms, skipped channels add nothing.
+pub fn max_measurement_time_us(osrs_t: Oversampling, osrs_p: Oversampling, osrs_h: Oversampling) -> u32 {
+    let channel = |osrs: Oversampling, overhead: u32| match osrs.factor() {
+        0 => 0,
+        factor => 2300 * factor + overhead
+    };
+    1250 + channel(osrs_t, 0) + channel(osrs_p, 575) + channel(osrs_h, 575)
+}
+
+// Status polling, after a forced conversion or a soft reset
+const STATUS_POLL_INTERVAL_US: u32 = 500;
+const STATUS_POLL_LIMIT: u32 = 20;
+// Power-on and soft reset start-up time (datasheet table 1)
+const STARTUP_TIME_US: u32 = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Temperature,
+    Pressure,
+    Humidity
+}
+
+// Compensated readings from a single conversion, None for skipped channels
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BME280Measurement {
+    pub temperature: Option<f64>,  // °C
+    pub pressure: Option<f64>,  // Pa
+    pub humidity: Option<f64>  // %RH
+}
+
+impl BME280Measurement {
+    // One channel's value, or the skipped channel that left it missing
+    pub fn get(&self, channel: Channel) -> Result<f64, Channel> {
+        let value = match channel {
+            Channel::Temperature => self.temperature,
+            Channel::Pressure => self.pressure,
+            Channel::Humidity => self.humidity
+        };
+        match (value, self.temperature) {
+            (Some(value), _) => Ok(value),
+            (None, None) => Err(Channel::Temperature),
+            (None, Some(_)) => Err(channel)
+        }
+    }
+}
+
 pub struct BME280<I2C> {
     dev: I2CWrapper<I2C>,
     calibration: calibration::Calibration,
-    t_fine: i32,
+    config: Option<BME280Config>,  // Last configuration written, if any
+    compensation: Compensation,
+    variant: Variant
 }
 
-impl<I2C: Write + WriteRead> BME280<I2C> {
+impl<I2C: I2c> BME280<I2C> {
 
-    // Create new BME280 device wrapper for I2C communication.
-    pub fn new(dev: I2C, address: u8) -> BME280<I2C> {
+    // Create new BME280 device wrapper for I2C communication. A BMP280 is
+    // accepted too, without its humidity channel.
+    pub fn new(dev: I2C, address: u8) -> Result<BME280<I2C>, BME280Error<I2C::Error>> {
         let mut wrapper = I2CWrapper::new(dev, address);
-        let calibration = calibration::Calibration::build(&mut wrapper);
-        BME280 { dev: wrapper, calibration: calibration, t_fine: 0 }
+        let variant = driver_variant(i2c::read_id(&mut wrapper)?)?;
+        let calibration = i2c::read_calibration(&mut wrapper, variant.has_humidity())?;
+        Ok(BME280 { dev: wrapper, calibration, config: None, compensation: Compensation::default(), variant })
     }
 
-    pub fn build(dev: I2C, address: u8) -> BME280<I2C> {
-        let mut sensor = BME280::new(dev, address);
-        sensor.start().unwrap();
-        sensor
+    pub fn build(dev: I2C, address: u8) -> Result<BME280<I2C>, BME280Error<I2C::Error>> {
+        let mut sensor = BME280::new(dev, address)?;
+        sensor.start()?;
+        Ok(sensor)
     }
 
     // Start all parameters from for the sensor
-    pub fn start(&mut self) -> Result<(), String> {
-        self.set_standby_time(StandyTime::Ms0_5).unwrap();
-        self.set_filter(Filter::Off).unwrap();
-        self.set_temperature_oversample(Oversampling::Ox1).unwrap();
-        self.set_pressure_oversample(Oversampling::Ox1).unwrap();
-        self.set_humidity_oversample(Oversampling::Ox1).unwrap();
-        self.set_mode(Mode::Normal).unwrap();
+    pub fn start(&mut self) -> Result<(), BME280Error<I2C::Error>> {
+        self.configure(BME280Config::default_for(self.variant))
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    // Write a whole configuration: sleep, write ctrl_hum and config, then
+    // ctrl_meas with the target mode (ctrl_hum only takes effect after it).
+    // A forced configuration leaves the chip asleep until `measure_forced`.
+    pub fn configure(&mut self, config: BME280Config) -> Result<(), BME280Error<I2C::Error>> {
+        check_humidity_config(self.variant, &config)?;
+        let standby = standby_code(self.variant, config.standby)?;
+        let osrs_t = u8::from(config.osrs_t);
+        let osrs_p = u8::from(config.osrs_p);
+        let mode = match config.mode {
+            Mode::Forced => Mode::Sleep,
+            mode => mode
+        };
+
+        i2c::write_ctrl_meas(&mut self.dev, fields::ctrl_meas(osrs_t, osrs_p, Mode::Sleep.into()))?;
+        if self.variant.has_humidity() {
+            i2c::write_field(&mut self.dev, fields::HUMIDITY_OVERSAMPLE, config.osrs_h.into())?;
+        }
+        i2c::write_config(&mut self.dev, standby, config.filter.into())?;
+        if mode != Mode::Sleep {
+            i2c::write_ctrl_meas(&mut self.dev, fields::ctrl_meas(osrs_t, osrs_p, mode.into()))?;
+        }
+
+        self.config = Some(config);
         Ok(())
     }
 
-    pub fn stop(&mut self) -> Result<(), String> {
-        self.set_mode(Mode::Sleep).unwrap();
+    pub fn config(&self) -> Option<BME280Config> {
+        self.config
+    }
+
+    // Decode the configuration the chip is actually running
+    pub fn read_config(&mut self) -> Result<BME280Config, BME280Error<I2C::Error>> {
+        let ctrl_hum = self.read_ctrl_humidity()?;
+        let ctrl_meas = i2c::read_ctrl_meas(&mut self.dev)?;
+        let config = i2c::read_config(&mut self.dev)?;
+        decode_config(self.variant, ctrl_hum, ctrl_meas, config)
+    }
+
+    // Check the chip still runs `expected`, e.g. after a brown-out
+    pub fn verify_config(&mut self, expected: &BME280Config) -> Result<(), BME280Error<I2C::Error>> {
+        let found = self.read_config()?;
+        if !expected.matches(&found) {
+            return Err(BME280Error::ConfigMismatch { expected: *expected, found })
+        }
         Ok(())
     }
 
+    pub fn calibration(&self) -> &calibration::Calibration {
+        &self.calibration
+    }
+
+    pub fn compensation(&self) -> Compensation {
+        self.compensation
+    }
+
+    // Select the formulas used to turn raw readings into units
+    pub fn set_compensation(&mut self, compensation: Compensation) {
+        self.compensation = compensation;
+    }
+
+    pub fn stop(&mut self) -> Result<(), BME280Error<I2C::Error>> {
+        self.set_mode(Mode::Sleep)
+    }
+
     // Get the ID of the chip
-    pub fn get_id(&mut self) -> Result<u8, String> {
-        let id = i2c::read_id(&mut self.dev);
-        if id != values::CHIP_ID {
-            return Err(String::from("ID doesn't match specs."))
+    pub fn get_id(&mut self) -> Result<u8, BME280Error<I2C::Error>> {
+        let id = i2c::read_id(&mut self.dev)?;
+        if id != u8::from(self.variant) {
+            return Err(BME280Error::ChipIdMismatch { expected: self.variant.into(), found: id })
         }
         Ok(id)
     }
 
-    // Reset device
-    pub fn reset(&mut self) -> Result<(), String> {
-        i2c::write_reset(&mut self.dev);
+    // Soft reset the device and bring it back to the state the driver expects:
+    // wait for the NVM copy, check the chip, reload the calibration and
+    // re-apply the last configuration.
+    pub fn reset<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), BME280Error<I2C::Error>> {
+        i2c::write_reset(&mut self.dev)?;
+        delay.delay_us(STARTUP_TIME_US);
+        if !self.wait_for_status(delay, fields::updating_bit)? {
+            return Err(BME280Error::NotReady)
+        }
+
+        self.get_id()?;
+        self.calibration = i2c::read_calibration(&mut self.dev, self.variant.has_humidity())?;
+        if let Some(config) = self.config {
+            self.configure(config)?;
+        }
         Ok(())
     }
 
     // Get mode from the device
-    pub fn get_mode(&mut self) -> Result<Mode, String> {
-        let mode = i2c::read_mode(&mut self.dev);
-        Ok(Mode::from(mode))
+    pub fn get_mode(&mut self) -> Result<Mode, BME280Error<I2C::Error>> {
+        decode_mode(i2c::read_ctrl_meas(&mut self.dev)?)
     }
 
     // Set the mode on the device
-    pub fn set_mode(&mut self, mode: Mode) -> Result<(),String> {
-        i2c::write_mode(&mut self.dev, u8::from(mode));
+    pub fn set_mode(&mut self, mode: Mode) -> Result<(), BME280Error<I2C::Error>> {
+        i2c::write_field(&mut self.dev, fields::MODE, u8::from(mode))?;
+        self.update_config(|config| config.mode = mode);
         Ok(())
     }
 
     // Is the device measuring
-   pub fn is_measuring(&mut self) -> Result<bool, String> {
-        Ok(i2c::read_measuring_bit(&mut self.dev) == 1)
+    pub fn is_measuring(&mut self) -> Result<bool, BME280Error<I2C::Error>> {
+        Ok(i2c::read_measuring_bit(&mut self.dev)? == 1)
     }
 
     // Is the device copying NVM data to image registers
-    pub fn is_updating(&mut self) -> Result<bool, String> {
+    pub fn is_updating(&mut self) -> Result<bool, BME280Error<I2C::Error>> {
         // Check bit 0 is set to 1
-        Ok((i2c::read_updating_bit(&mut self.dev)) == 1)
+        Ok(i2c::read_updating_bit(&mut self.dev)? == 1)
     }
 
-    pub fn set_humidity_oversample(&mut self, rate: Oversampling) -> Result<(), String> {
-        let device_mode = self.get_mode().unwrap();
-        self.set_mode(Mode::Sleep).unwrap();
-        i2c::write_humidity_oversample(&mut self.dev, u8::from(rate));
-        self.set_mode(device_mode).unwrap();
+    pub fn set_humidity_oversample(&mut self, rate: Oversampling) -> Result<(), BME280Error<I2C::Error>> {
+        require_humidity(self.variant)?;
+        self.while_sleeping(|dev| i2c::write_field(dev, fields::HUMIDITY_OVERSAMPLE, u8::from(rate)))?;
+        self.update_config(|config| config.osrs_h = rate);
         Ok(())
     }
 
-    pub fn set_temperature_oversample(&mut self, rate: Oversampling) -> Result<(), String> {
-        let device_mode = self.get_mode().unwrap();
-        self.set_mode(Mode::Sleep).unwrap();
-        i2c::write_temperature_oversample(&mut self.dev, u8::from(rate));
-        self.set_mode(device_mode).unwrap();
+    pub fn set_temperature_oversample(&mut self, rate: Oversampling) -> Result<(), BME280Error<I2C::Error>> {
+        self.while_sleeping(|dev| i2c::write_field(dev, fields::TEMPERATURE_OVERSAMPLE, u8::from(rate)))?;
+        self.update_config(|config| config.osrs_t = rate);
         Ok(())
     }
 
-    pub fn set_pressure_oversample(&mut self, rate: Oversampling) -> Result<(), String> {
-        let device_mode = self.get_mode().unwrap();
-        self.set_mode(Mode::Sleep).unwrap();
-        i2c::write_pressure_oversample(&mut self.dev, u8::from(rate));
-        self.set_mode(device_mode).unwrap();
+    pub fn set_pressure_oversample(&mut self, rate: Oversampling) -> Result<(), BME280Error<I2C::Error>> {
+        self.while_sleeping(|dev| i2c::write_field(dev, fields::PRESSURE_OVERSAMPLE, u8::from(rate)))?;
+        self.update_config(|config| config.osrs_p = rate);
+        Ok(())
+    }
+
+    pub fn get_humidity_oversample(&mut self) -> Result<Oversampling, BME280Error<I2C::Error>> {
+        require_humidity(self.variant)?;
+        Ok(fields::humidity_oversample(i2c::read_ctrl_humidity(&mut self.dev)?).into())
+    }
 
+    pub fn get_temperature_oversample(&mut self) -> Result<Oversampling, BME280Error<I2C::Error>> {
+        Ok(fields::temperature_oversample(i2c::read_ctrl_meas(&mut self.dev)?).into())
+    }
+
+    pub fn get_pressure_oversample(&mut self) -> Result<Oversampling, BME280Error<I2C::Error>> {
+        Ok(fields::pressure_oversample(i2c::read_ctrl_meas(&mut self.dev)?).into())
+    }
+
+    pub fn get_standby_time(&mut self) -> Result<StandyTime, BME280Error<I2C::Error>> {
+        decode_standby_time(self.variant, i2c::read_config(&mut self.dev)?)
+    }
+
+    pub fn get_filter(&mut self) -> Result<Filter, BME280Error<I2C::Error>> {
+        Ok(fields::filter(i2c::read_config(&mut self.dev)?).into())
+    }
+
+    pub fn set_standby_time(&mut self, standby: StandyTime) -> Result<(), BME280Error<I2C::Error>> {
+        i2c::write_field(&mut self.dev, fields::STANDBY_TIME, standby_code(self.variant, standby)?)?;
+        self.update_config(|config| config.standby = standby);
         Ok(())
     }
 
-    pub fn set_standby_time(&mut self, standby: StandyTime) -> Result<(), String> {
-        i2c::write_standby_time(&mut self.dev, u8::from(standby));
+    pub fn set_filter(&mut self, filter: Filter) -> Result<(), BME280Error<I2C::Error>> {
+        i2c::write_field(&mut self.dev, fields::FILTER, u8::from(filter))?;
+        self.update_config(|config| config.filter = filter);
         Ok(())
     }
 
-    pub fn set_filter(&mut self, filter: Filter) -> Result<(), String> {
-        i2c::write_filter(&mut self.dev, u8::from(filter));
+    // Read all data registers in one burst and compensate them together, so
+    // pressure and humidity always use the temperature of their conversion.
+    pub fn measure(&mut self) -> Result<BME280Measurement, BME280Error<I2C::Error>> {
+        let raw = self.read_raw()?;
+        Ok(self.calibration.compensate(raw, self.compensation))
+    }
+
+    // Uncompensated readings, to log alongside `calibration()` and compensate
+    // offline with `Calibration::compensate`.
+    pub fn read_raw(&mut self) -> Result<RawData, BME280Error<I2C::Error>> {
+        Ok(i2c::read_raw_data(&mut self.dev, self.variant.has_humidity())?)
+    }
+
+    // One-shot conversion in forced mode. Waits the maximum measurement time
+    // for the configured oversampling, then polls until the chip is done. The
+    // chip goes back to sleep on its own after a forced conversion.
+    pub fn measure_forced<D: DelayNs>(&mut self, delay: &mut D) -> Result<BME280Measurement, BME280Error<I2C::Error>> {
+        let ctrl_hum = self.read_ctrl_humidity()?;
+        let ctrl_meas = i2c::read_ctrl_meas(&mut self.dev)?;
+        i2c::write_ctrl_meas(&mut self.dev, fields::with_mode(ctrl_meas, Mode::Forced.into()))?;
+
+        delay.delay_us(forced_wait_us(ctrl_meas, ctrl_hum));
+        if !self.wait_for_status(delay, fields::measuring_bit)? {
+            i2c::write_field(&mut self.dev, fields::MODE, Mode::Sleep.into())?;
+            return Err(BME280Error::NotReady)
+        }
+
+        self.measure()
+    }
+
+    pub fn get_temperature_celsius(&mut self) -> Result<f64, BME280Error<I2C::Error>> {
+        self.measure()?.get(Channel::Temperature).map_err(BME280Error::ChannelSkipped)
+    }
+
+    pub fn get_pressure_pascal(&mut self) -> Result<f64, BME280Error<I2C::Error>> {
+        self.measure()?.get(Channel::Pressure).map_err(BME280Error::ChannelSkipped)
+    }
+
+    pub fn get_humidity_relative(&mut self) -> Result<f64, BME280Error<I2C::Error>> {
+        require_humidity(self.variant)?;
+        self.measure()?.get(Channel::Humidity).map_err(BME280Error::ChannelSkipped)
+    }
+
+    // Poll a status bit until it clears, false if it is still set at the limit
+    fn wait_for_status<D: DelayNs>(&mut self, delay: &mut D, bit: fn(u8) -> u8) -> Result<bool, BME280Error<I2C::Error>> {
+        for _ in 0..STATUS_POLL_LIMIT {
+            if bit(i2c::read_status(&mut self.dev)?) == 0 {
+                return Ok(true)
+            }
+            delay.delay_us(STATUS_POLL_INTERVAL_US);
+        }
+        Ok(bit(i2c::read_status(&mut self.dev)?) == 0)
+    }
+
+    // Oversampling can only be changed in sleep mode, the mode is restored after
+    fn while_sleeping(&mut self, write: impl FnOnce(&mut I2CWrapper<I2C>) -> Result<(), I2CError<I2C::Error>>) -> Result<(), BME280Error<I2C::Error>> {
+        let device_mode = self.get_mode()?;
+        i2c::write_field(&mut self.dev, fields::MODE, Mode::Sleep.into())?;
+        write(&mut self.dev)?;
+        i2c::write_field(&mut self.dev, fields::MODE, device_mode.into())?;
         Ok(())
     }
 
-    // Get temperature from the sensor.
-    pub fn get_temperature_celsius(&mut self) -> Result<f64, String> {
-        let adc_t = i2c::get_temperature_raw(&mut self.dev);
-        self.t_fine = self.calibration.temperature.compensate_temperature(adc_t as i32);
-        let output = (self.t_fine * 5 + 128) >> 8;
-        Ok(f64::from(output) / 100.0)
+    // ctrl_hum reads as 0 (humidity skipped) on chips without it
+    fn read_ctrl_humidity(&mut self) -> Result<u8, BME280Error<I2C::Error>> {
+        if !self.variant.has_humidity() {
+            return Ok(0)
+        }
+        Ok(i2c::read_ctrl_humidity(&mut self.dev)?)
     }
 
-    pub fn get_pressure_pascal(&mut self) -> Result<f64, String> {
-        let adc_p = i2c::get_pressure_raw(&mut self.dev);
-        let pressure = self.calibration.pressure.compensate_pressure(adc_p as i32, self.t_fine);
-        Ok(f64::from(pressure) / 256.0)
+    // Keep the stored configuration in line with single-field setters
+    fn update_config(&mut self, update: impl FnOnce(&mut BME280Config)) {
+        if let Some(config) = self.config.as_mut() {
+            update(config);
+        }
+    }
+
+}
+
+// Shared by the blocking and async drivers.
+fn driver_variant<E>(id: u8) -> Result<Variant, BME280Error<E>> {
+    match Variant::try_from(id) {
+        Ok(variant) if !variant.has_gas() => Ok(variant),
+        _ => Err(BME280Error::ChipIdMismatch { expected: values::CHIP_ID, found: id })
+    }
+}
+
+fn require_humidity<E>(variant: Variant) -> Result<(), BME280Error<E>> {
+    if !variant.has_humidity() {
+        return Err(BME280Error::ChannelUnsupported(Channel::Humidity))
+    }
+    Ok(())
+}
+
+fn check_humidity_config<E>(variant: Variant, config: &BME280Config) -> Result<(), BME280Error<E>> {
+    if config.osrs_h != Oversampling::Skipped {
+        require_humidity(variant)?;
     }
+    Ok(())
+}
+
+fn decode_mode<E>(ctrl_meas: u8) -> Result<Mode, BME280Error<E>> {
+    Mode::try_from(fields::mode(ctrl_meas)).map_err(|value| BME280Error::InvalidRegisterValue {
+        register: constants::registers::CTRL_MEAS_REG,
+        value
+    })
+}
+
+fn standby_code<E>(variant: Variant, standby: StandyTime) -> Result<u8, BME280Error<E>> {
+    standby.code(variant).ok_or(BME280Error::StandbyUnsupported(standby))
+}
+
+fn decode_standby_time<E>(variant: Variant, config: u8) -> Result<StandyTime, BME280Error<E>> {
+    StandyTime::from_code(fields::standby_time(config), variant).map_err(|value| BME280Error::InvalidRegisterValue {
+        register: constants::registers::CONFIG_REG,
+        value
+    })
+}
 
-    pub fn get_humidity_relative(&mut self) -> Result<f64, String> {
-        let adc_h = i2c::get_humidity_raw(&mut self.dev);
-        let humidity = self.calibration.humidity.compensate_humidity(adc_h as i32, self.t_fine);
+fn decode_config<E>(variant: Variant, ctrl_hum: u8, ctrl_meas: u8, config: u8) -> Result<BME280Config, BME280Error<E>> {
+    Ok(BME280Config {
+        mode: decode_mode(ctrl_meas)?,
+        osrs_t: fields::temperature_oversample(ctrl_meas).into(),
+        osrs_p: fields::pressure_oversample(ctrl_meas).into(),
+        osrs_h: fields::humidity_oversample(ctrl_hum).into(),
+        filter: fields::filter(config).into(),
+        standby: decode_standby_time(variant, config)?
+    })
+}
+
+fn forced_wait_us(ctrl_meas: u8, ctrl_hum: u8) -> u32 {
+    max_measurement_time_us(
+        fields::temperature_oversample(ctrl_meas).into(),
+        fields::pressure_oversample(ctrl_meas).into(),
+        fields::humidity_oversample(ctrl_hum).into()
+    )
+}
 
-        Ok(f64::from(humidity) / 1024.0)
+#[cfg(feature = "std")]
+impl<I2C: I2c> Sensor for BME280<I2C> {
+    type BusError = I2C::Error;
+
+    fn id(&self) -> SensorId {
+        SensorId::new(self.variant.name(), self.dev.address())
     }
 
+    fn sample(&mut self) -> Result<Vec<Measurement>, SensorError<I2C::Error>> {
+        let timestamp = Utc::now();
+        let measurement = self.measure()?;
+
+        // Skipped channels are left out
+        Ok([
+            (Quantity::Temperature, measurement.temperature),
+            (Quantity::Pressure, measurement.pressure),
+            (Quantity::RelativeHumidity, measurement.humidity),
+        ].into_iter()
+            .filter_map(|(quantity, value)| Some(Measurement::new(self.id(), quantity, value?, timestamp)))
+            .collect())
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    #[cfg(feature = "std")]
+    use crate::{measurement::Quantity, sensors::Sensor};
 
-    use super::{Address, BME280, constants::registers};
+    use super::{detect, Address, BME280, BME280Config, BME280Error, BME280Measurement, Channel, Compensation, Filter, Mode, Oversampling, StandyTime, Variant, constants::registers};
+
+    fn data_burst(address: u8, block: [u8; 8]) -> I2cTransaction {
+        I2cTransaction::write_read(address, vec![registers::PRESSURE_MSB_REG], block.to_vec())
+    }
 
     #[test]
     fn read_humidity() {
         let address: u8 = Address::Default.into();
         let mut expectations = get_mock_calibration(address);
-        expectations.push(
-            I2cTransaction::write_read(address, vec![0xFD], vec![110]),    
-        );
-        expectations.push(
-            I2cTransaction::write_read(address, vec![0xFE], vec![213]),
-        );
+        expectations.push(data_burst(address, [0, 0, 0, 128, 189, 0, 110, 213]));
 
-        let i2c = I2cMock::new(&expectations);
+        let mut i2c = I2cMock::new(&expectations);
 
-        let mut bme280_sensor = BME280::new(i2c, address); // = BME280::build(i2c, addresses::DEFAULT);
-        bme280_sensor.t_fine = 0;
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
         let humidity = bme280_sensor.get_humidity_relative().unwrap();
-        
-        assert!(humidity - 46.159 < 0.1);
+
+        // Integer compensation, 47765 / 1024 %RH with the t_fine of the same burst
+        assert_eq!(humidity, 46.6455078125);
+        i2c.done();
     }
 
     #[test]
     fn read_temperature() {
         let address: u8 = Address::Default.into();
         let mut expectations = get_mock_calibration(address);
-        expectations.push(
-            I2cTransaction::write_read(address, vec![registers::TEMPERATURE_MSB_REG], vec![0])
-        );
-        expectations.push(
-            I2cTransaction::write_read(address, vec![registers::TEMPERATURE_LSB_REG], vec![0])
-        );
-        expectations.push(
-            I2cTransaction::write_read(address, vec![registers::TEMPERATURE_XLSB_REG], vec![0])
-        );
-        
-        let i2c = I2cMock::new(&expectations);
+        expectations.push(data_burst(address, [0, 0, 0, 128, 189, 0, 0, 0]));
+
+        let mut i2c = I2cMock::new(&expectations);
 
-        let mut bme280_sensor = BME280::new(i2c, address);
-        bme280_sensor.t_fine = 0;
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
         let temperature = bme280_sensor.get_temperature_celsius().unwrap();
 
         assert!(temperature > -100.);
         assert!(temperature < 100.);
+        i2c.done();
     }
 
     #[test]
     fn read_pressure() {
         let address: u8 = Address::Default.into();
         let mut expectations = get_mock_calibration(address);
-        expectations.push(
-            I2cTransaction::write_read(address, vec![registers::PRESSURE_MSB_REG], vec![0])
-        );
-        expectations.push(
-            I2cTransaction::write_read(address, vec![registers::PRESSURE_LSB_REG], vec![0])
+        expectations.push(data_burst(address, [82, 79, 0, 128, 189, 0, 0, 0]));
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        let pressure = bme280_sensor.get_pressure_pascal().unwrap();
+
+        assert!(pressure > 0.0);
+        i2c.done();
+    }
+
+    #[test]
+    fn measure_uses_one_burst() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.push(data_burst(address, [82, 79, 0, 128, 189, 0, 117, 97]));
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        let measurement = bme280_sensor.measure().unwrap();
+
+        assert_eq!(measurement.temperature, Some(22.81));
+        assert_eq!(measurement.pressure, Some(101602.51171875));
+        assert_eq!(measurement.humidity, Some(56.005859375));
+        i2c.done();
+    }
+
+    #[test]
+    fn skipped_channels_are_missing() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        // Pressure and humidity skipped
+        expectations.push(data_burst(address, [0x80, 0x00, 0x00, 128, 189, 0, 0x80, 0x00]));
+        expectations.push(data_burst(address, [0x80, 0x00, 0x00, 128, 189, 0, 0x80, 0x00]));
+        // Temperature skipped, pressure and humidity cannot be compensated
+        expectations.push(data_burst(address, [82, 79, 0, 0x80, 0x00, 0x00, 117, 97]));
+        expectations.push(data_burst(address, [82, 79, 0, 0x80, 0x00, 0x00, 117, 97]));
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        let measurement = bme280_sensor.measure().unwrap();
+        assert_eq!(measurement.temperature, Some(22.81));
+        assert_eq!(measurement.pressure, None);
+        assert_eq!(measurement.humidity, None);
+        assert!(matches!(
+            bme280_sensor.get_pressure_pascal(),
+            Err(BME280Error::ChannelSkipped(Channel::Pressure))
+        ));
+
+        assert_eq!(bme280_sensor.measure().unwrap(), BME280Measurement::default());
+        assert!(matches!(
+            bme280_sensor.get_humidity_relative(),
+            Err(BME280Error::ChannelSkipped(Channel::Temperature))
+        ));
+        i2c.done();
+    }
+
+    #[test]
+    fn raw_readings_compensate_offline() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        for _ in 0..2 {
+            expectations.push(data_burst(address, [82, 79, 0, 128, 189, 0, 117, 97]));
+        }
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        let raw = bme280_sensor.read_raw().unwrap();
+        let calibration = *bme280_sensor.calibration();
+
+        assert_eq!(calibration.compensate(raw, Compensation::Int64), bme280_sensor.measure().unwrap());
+        i2c.done();
+    }
+
+    #[test]
+    fn compensation_backends() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        for _ in 0..3 {
+            expectations.push(data_burst(address, [82, 79, 0, 128, 189, 0, 117, 97]));
+        }
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        assert_eq!(bme280_sensor.compensation(), Compensation::Int64);
+        let values = |measurement: BME280Measurement| (
+            measurement.temperature.unwrap(), measurement.pressure.unwrap(), measurement.humidity.unwrap()
         );
+        let int64 = values(bme280_sensor.measure().unwrap());
+        bme280_sensor.set_compensation(Compensation::Int32);
+        let int32 = values(bme280_sensor.measure().unwrap());
+        bme280_sensor.set_compensation(Compensation::Float);
+        let float = values(bme280_sensor.measure().unwrap());
+
+        assert_eq!(int32.0, int64.0);
+        assert_eq!(int32.2, int64.2);
+        assert_eq!(int32.1, int32.1.trunc());
+        assert!((int32.1 - float.1).abs() <= 4.0);
+        assert!((int64.1 - float.1).abs() < 0.5);
+        assert!((int64.0 - float.0).abs() < 0.01);
+        assert!((int64.2 - float.2).abs() < 0.05);
+        i2c.done();
+    }
+
+    #[test]
+    fn max_measurement_time() {
+        use super::{max_measurement_time_us, Oversampling};
+
+        // Datasheet table 15 and appendix B
+        assert_eq!(max_measurement_time_us(Oversampling::Ox1, Oversampling::Ox1, Oversampling::Ox1), 9300);
+        assert_eq!(max_measurement_time_us(Oversampling::Ox1, Oversampling::Skipped, Oversampling::Skipped), 3550);
+        assert_eq!(max_measurement_time_us(Oversampling::Ox2, Oversampling::Ox16, Oversampling::Ox1), 46100);
+        assert_eq!(max_measurement_time_us(Oversampling::Ox16, Oversampling::Ox16, Oversampling::Ox16), 112800);
+    }
+
+    #[test]
+    fn measure_forced_polls_until_done() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.extend([
+            I2cTransaction::write_read(address, vec![registers::CTRL_HUMIDITY_REG], vec![0x01]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x24]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x25]),
+            I2cTransaction::write_read(address, vec![registers::STAT_REG], vec![0x08]),
+            I2cTransaction::write_read(address, vec![registers::STAT_REG], vec![0x00]),
+            data_burst(address, [82, 79, 0, 128, 189, 0, 117, 97]),
+        ]);
+        let delays = [DelayTransaction::delay_us(9300), DelayTransaction::delay_us(500)];
+
+        let mut i2c = I2cMock::new(&expectations);
+        let mut delay = CheckedDelay::new(&delays);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        let measurement = bme280_sensor.measure_forced(&mut delay).unwrap();
+
+        assert_eq!(measurement.temperature, Some(22.81));
+        i2c.done();
+        delay.done();
+    }
+
+    #[test]
+    fn measure_forced_times_out_asleep() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.extend([
+            I2cTransaction::write_read(address, vec![registers::CTRL_HUMIDITY_REG], vec![0x00]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x20]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x21]),
+        ]);
+        expectations.extend((0..=20).map(|_| I2cTransaction::write_read(address, vec![registers::STAT_REG], vec![0x08])));
+        expectations.extend([
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x21]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x20]),
+        ]);
+        let mut delays = vec![DelayTransaction::delay_us(3550)];
+        delays.extend((0..20).map(|_| DelayTransaction::delay_us(500)));
+
+        let mut i2c = I2cMock::new(&expectations);
+        let mut delay = CheckedDelay::new(&delays);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        let result = bme280_sensor.measure_forced(&mut delay);
+
+        assert!(matches!(result, Err(BME280Error::NotReady)));
+        i2c.done();
+        delay.done();
+    }
+
+    #[test]
+    fn configure_in_one_sequence() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.extend([
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x54]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_HUMIDITY_REG], vec![0x00]),
+            I2cTransaction::write(address, vec![registers::CTRL_HUMIDITY_REG, 0x01]),
+            // SPI 3 wire bit is kept
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0x01]),
+            I2cTransaction::write(address, vec![registers::CONFIG_REG, 0x11]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x57]),
+            // Single field setters keep the stored configuration up to date
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0x11]),
+            I2cTransaction::write(address, vec![registers::CONFIG_REG, 0x01]),
+        ]);
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        assert_eq!(bme280_sensor.config(), None);
+        bme280_sensor.configure(BME280Config::indoor_navigation()).unwrap();
+        bme280_sensor.set_filter(Filter::Off).unwrap();
+
+        assert_eq!(bme280_sensor.config(), Some(BME280Config { filter: Filter::Off, ..BME280Config::indoor_navigation() }));
+        i2c.done();
+    }
+
+    #[test]
+    fn forced_preset_stays_asleep() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.extend([
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x24]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_HUMIDITY_REG], vec![0x00]),
+            I2cTransaction::write(address, vec![registers::CTRL_HUMIDITY_REG, 0x01]),
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0x00]),
+            I2cTransaction::write(address, vec![registers::CONFIG_REG, 0xA0]),
+        ]);
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        bme280_sensor.configure(BME280Config::weather_monitoring()).unwrap();
+
+        assert_eq!(bme280_sensor.config().map(|config| config.mode), Some(Mode::Forced));
+        i2c.done();
+    }
+
+    #[test]
+    fn read_back_config() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.extend([
+            I2cTransaction::write_read(address, vec![registers::CTRL_HUMIDITY_REG], vec![0x01]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x57]),
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0x10]),
+            // After a brown-out every register is back to its reset value
+            I2cTransaction::write_read(address, vec![registers::CTRL_HUMIDITY_REG], vec![0x00]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x00]),
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0x00]),
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0xAC]),
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0xAC]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x57]),
+        ]);
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        bme280_sensor.verify_config(&BME280Config::indoor_navigation()).unwrap();
+        let result = bme280_sensor.verify_config(&BME280Config::indoor_navigation());
+        assert!(matches!(
+            result,
+            Err(BME280Error::ConfigMismatch { found: BME280Config { mode: Mode::Sleep, osrs_p: Oversampling::Skipped, .. }, .. })
+        ));
+        assert_eq!(bme280_sensor.get_standby_time().unwrap(), StandyTime::Ms1000);
+        assert_eq!(bme280_sensor.get_filter().unwrap(), Filter::C8);
+        assert_eq!(bme280_sensor.get_pressure_oversample().unwrap(), Oversampling::Ox16);
+        i2c.done();
+    }
+
+    #[test]
+    fn reset_reloads_calibration_and_config() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        // Default configuration from build()
+        let configure = [
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x24]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_HUMIDITY_REG], vec![0x00]),
+            I2cTransaction::write(address, vec![registers::CTRL_HUMIDITY_REG, 0x01]),
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0x00]),
+            I2cTransaction::write(address, vec![registers::CONFIG_REG, 0x00]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x27]),
+        ];
+        expectations.extend(configure.clone());
+        expectations.extend([
+            I2cTransaction::write(address, vec![registers::RST_REG, 0xB6]),
+            I2cTransaction::write_read(address, vec![registers::STAT_REG], vec![0x01]),
+            I2cTransaction::write_read(address, vec![registers::STAT_REG], vec![0x00]),
+        ]);
+        expectations.extend(get_mock_calibration(address));
+        expectations.extend(configure);
+        let delays = [DelayTransaction::delay_us(2000), DelayTransaction::delay_us(500)];
+
+        let mut i2c = I2cMock::new(&expectations);
+        let mut delay = CheckedDelay::new(&delays);
+
+        let mut bme280_sensor = BME280::build(i2c.clone(), address).unwrap();
+        bme280_sensor.reset(&mut delay).unwrap();
+
+        assert_eq!(bme280_sensor.config(), Some(BME280Config::default()));
+        i2c.done();
+        delay.done();
+    }
+
+    #[test]
+    fn reset_times_out_on_nvm_copy() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.push(I2cTransaction::write(address, vec![registers::RST_REG, 0xB6]));
+        expectations.extend((0..=20).map(|_| I2cTransaction::write_read(address, vec![registers::STAT_REG], vec![0x01])));
+        let mut delays = vec![DelayTransaction::delay_us(2000)];
+        delays.extend((0..20).map(|_| DelayTransaction::delay_us(500)));
+
+        let mut i2c = I2cMock::new(&expectations);
+        let mut delay = CheckedDelay::new(&delays);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        let result = bme280_sensor.reset(&mut delay);
+
+        assert!(matches!(result, Err(BME280Error::NotReady)));
+        i2c.done();
+        delay.done();
+    }
+
+    #[test]
+    fn chip_id_mismatch() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
         expectations.push(
-            I2cTransaction::write_read(address, vec![registers::PRESSURE_XLSB_REG], vec![0])
+            I2cTransaction::write_read(address, vec![registers::CHIP_ID_REG], vec![0x58])
         );
-        
-        let i2c = I2cMock::new(&expectations);
 
-        let mut bme280_sensor = BME280::new(i2c, address);
-        bme280_sensor.t_fine = 0;
-        let pressure = bme280_sensor.get_pressure_pascal().unwrap();
+        let mut i2c = I2cMock::new(&expectations);
 
-        assert!(pressure > 0.0);
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        let result = bme280_sensor.get_id();
+
+        assert!(matches!(result, Err(BME280Error::ChipIdMismatch { expected: 0x60, found: 0x58 })));
+        i2c.done();
+    }
+
+    #[test]
+    fn detect_variants() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::CHIP_ID_REG], vec![0x58]),
+            I2cTransaction::write_read(address, vec![registers::CHIP_ID_REG], vec![0x61]),
+            I2cTransaction::write_read(address, vec![registers::CHIP_ID_REG], vec![0x55]),
+            I2cTransaction::write_read(address, vec![registers::CHIP_ID_REG], vec![0x61]),
+        ];
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        assert_eq!(detect(&mut i2c, address).unwrap(), Variant::BMP280);
+        assert_eq!(detect(&mut i2c, address).unwrap(), Variant::BME680);
+        assert!(matches!(detect(&mut i2c, address), Err(BME280Error::ChipIdMismatch { found: 0x55, .. })));
+        // The BME680 has its own driver
+        assert!(matches!(BME280::new(i2c.clone(), address), Err(BME280Error::ChipIdMismatch { found: 0x61, .. })));
+        i2c.done();
+    }
+
+    #[test]
+    fn bmp280_has_no_humidity() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::CHIP_ID_REG], vec![0x58]),
+            I2cTransaction::write_read(address, vec![registers::DIG_T1_LSB_REG], MOCK_TP_BLOCK.to_vec()),
+            // Default configuration, without ctrl_hum
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x24]),
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0x00]),
+            I2cTransaction::write(address, vec![registers::CONFIG_REG, 0x00]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x27]),
+            // Temperature and pressure registers only
+            I2cTransaction::write_read(address, vec![registers::PRESSURE_MSB_REG], vec![82, 79, 0, 128, 189, 0]),
+        ];
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut bmp280_sensor = BME280::build(i2c.clone(), address).unwrap();
+        assert_eq!(bmp280_sensor.variant(), Variant::BMP280);
+        assert_eq!(bmp280_sensor.config(), Some(BME280Config::default().without_humidity()));
+
+        let measurement = bmp280_sensor.measure().unwrap();
+        assert_eq!(measurement.temperature, Some(22.81));
+        assert_eq!(measurement.pressure, Some(101602.51171875));
+        assert_eq!(measurement.humidity, None);
+
+        assert!(matches!(
+            bmp280_sensor.get_humidity_relative(),
+            Err(BME280Error::ChannelUnsupported(Channel::Humidity))
+        ));
+        assert!(matches!(
+            bmp280_sensor.set_humidity_oversample(Oversampling::Ox1),
+            Err(BME280Error::ChannelUnsupported(Channel::Humidity))
+        ));
+        assert!(matches!(
+            bmp280_sensor.configure(BME280Config::weather_monitoring()),
+            Err(BME280Error::ChannelUnsupported(Channel::Humidity))
+        ));
+        i2c.done();
     }
 
-    fn get_mock_calibration(address: u8) -> Vec<I2cTransaction> {
-        let expectations = vec![
-            I2cTransaction::write_read(address, vec![registers::DIG_T1_LSB_REG], ((28485_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![registers::DIG_T1_MSB_REG], ((28485_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-            // T2 calibration
-            I2cTransaction::write_read(address, vec![registers::DIG_T2_LSB_REG], ((26735_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![registers::DIG_T2_MSB_REG], ((26735_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-            // T3 calibration
-            I2cTransaction::write_read(address, vec![registers::DIG_T3_LSB_REG], ((50_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![registers::DIG_T3_MSB_REG], ((50_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-
-            // Pressure calibration
-            // P1 calibration
-            I2cTransaction::write_read(address, vec![0x8E], ((36738_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![0x8F], ((36738_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-            // P2 calibration
-            I2cTransaction::write_read(address, vec![0x90], ((-10635_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![0x91], ((-10635_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-            // P3 calibration
-            I2cTransaction::write_read(address, vec![0x92], ((3024_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![0x93], ((3024_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-            // P4 calibration
-            I2cTransaction::write_read(address, vec![0x94], ((6980_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![0x95], ((6980_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-            // P5 calibration
-            I2cTransaction::write_read(address, vec![0x96], ((-4_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![0x97], ((-4_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-            // P6 calibration
-            I2cTransaction::write_read(address, vec![0x98], ((-7_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![0x99], ((-7_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-            // P7 calibration
-            I2cTransaction::write_read(address, vec![0x9A], ((9900_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![0x9B], ((9900_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-            // P8 calibration
-            I2cTransaction::write_read(address, vec![0x9C], ((-10230_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![0x9D], ((-10230_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-            // P9 calibration
-            I2cTransaction::write_read(address, vec![0x9E], ((4285_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![0x9F], ((4285_i64 & 0xFF00 >> 8) as u8).to_be_bytes().to_vec()),
-
-            // TODO check all calibration values from python for sample case
-            // Humidity calibration
-            // H1 calibration
-            I2cTransaction::write_read(address, vec![registers::DIG_H1_REG], ((75_i64 & 0xFF) as u8).to_be_bytes().to_vec()),
-            // H2 calibration
-            I2cTransaction::write_read(address, vec![registers::DIG_H2_LSB_REG], ((109 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![registers::DIG_H2_MSB_REG], ((1 & 0xFF) as u8).to_be_bytes().to_vec()),
-            // H3 calibration
-            I2cTransaction::write_read(address, vec![registers::DIG_H3_REG], ((0 & 0xFF) as u8).to_be_bytes().to_vec()),
-            // H4 calibration
-            I2cTransaction::write_read(address, vec![registers::DIG_H4_MSB_REG], ((19 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![registers::DIG_H4_LSB_REG], ((40 & 0xFF) as u8).to_be_bytes().to_vec()),
-            // H5 calibration
-            I2cTransaction::write_read(address, vec![registers::DIG_H5_MSB_REG], ((3 & 0xFF) as u8).to_be_bytes().to_vec()),
-            I2cTransaction::write_read(address, vec![registers::DIG_H4_LSB_REG], ((40 & 0xFF) as u8).to_be_bytes().to_vec()),
-            // H6 calibration
-            I2cTransaction::write_read(address, vec![registers::DIG_H6_REG], ((30 & 0xFF) as u8).to_be_bytes().to_vec()),
+    #[test]
+    fn invalid_standby_time() {
+        assert_eq!(StandyTime::from_code(7, Variant::BME280), Ok(StandyTime::Ms20));
+        assert_eq!(StandyTime::from_code(8, Variant::BME280), Err(8));
+        assert_eq!(StandyTime::Ms4000.code(Variant::BME280), None);
+    }
+
+    #[test]
+    fn bmp280_standby_times() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::CHIP_ID_REG], vec![0x58]),
+            I2cTransaction::write_read(address, vec![registers::DIG_T1_LSB_REG], MOCK_TP_BLOCK.to_vec()),
+            // 0b110 is 2000 ms on a BMP280
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0xC0]),
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0xC0]),
+            I2cTransaction::write(address, vec![registers::CONFIG_REG, 0xE0]),
         ];
-        return expectations
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut bmp280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        assert_eq!(bmp280_sensor.get_standby_time().unwrap(), StandyTime::Ms2000);
+        bmp280_sensor.set_standby_time(StandyTime::Ms4000).unwrap();
+        // Rejected before touching the bus
+        assert!(matches!(
+            bmp280_sensor.set_standby_time(StandyTime::Ms10),
+            Err(BME280Error::StandbyUnsupported(StandyTime::Ms10))
+        ));
+        assert!(matches!(
+            bmp280_sensor.configure(BME280Config { standby: StandyTime::Ms20, ..BME280Config::default().without_humidity() }),
+            Err(BME280Error::StandbyUnsupported(StandyTime::Ms20))
+        ));
+        i2c.done();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn sample_all_quantities() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.push(data_burst(address, [82, 79, 0, 128, 189, 0, 117, 97]));
+
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut bme280_sensor = BME280::new(i2c.clone(), address).unwrap();
+        let measurements = bme280_sensor.sample().unwrap();
+        let quantities: Vec<Quantity> = measurements.iter().map(|m| m.quantity).collect();
+
+        assert_eq!(quantities, vec![Quantity::Temperature, Quantity::Pressure, Quantity::RelativeHumidity]);
+        assert!(measurements.iter().all(|m| m.timestamp == measurements[0].timestamp));
+        i2c.done();
+    }
+
+    // Calibration image returned by the mocked chip, registers 0x88..0xA1
+    // and 0xE1..0xE7.
+    pub(super) const MOCK_TP_BLOCK: [u8; 26] = [
+        0x45, 0x6F, 0x6F, 0x68, 0x32, 0x00,  // T1 28485, T2 26735, T3 50
+        0x82, 0x8F, 0x75, 0xD6, 0xD0, 0x0B, 0x44, 0x1B, 0xFC, 0xFF,  // P1..P5
+        0xF9, 0xFF, 0xAC, 0x26, 0x0A, 0xD8, 0xBD, 0x10,  // P6..P9
+        0x00, 75,  // Unused, H1
+    ];
+    pub(super) const MOCK_H_BLOCK: [u8; 7] = [0x6D, 0x01, 0x00, 0x13, 0x28, 0x03, 0x1E];
+
+    // Chip ID then calibration, as read by `new` and `reset`
+    pub(super) fn get_mock_calibration(address: u8) -> Vec<I2cTransaction> {
+        vec![
+            I2cTransaction::write_read(address, vec![registers::CHIP_ID_REG], vec![0x60]),
+            I2cTransaction::write_read(address, vec![registers::DIG_T1_LSB_REG], MOCK_TP_BLOCK.to_vec()),
+            I2cTransaction::write_read(address, vec![registers::DIG_H2_LSB_REG], MOCK_H_BLOCK.to_vec()),
+        ]
     }
 
     #[test]
     fn random() {
-        dbg!((((50_i64 & 0xFF)) as u8).to_be_bytes().to_vec());
+        dbg!(((50_i64 & 0xFF) as u8).to_be_bytes().to_vec());
         dbg!((((50_i64 & 0xFF00) >> 8) as u8).to_be_bytes().to_vec());
     }
 }
\ No newline at end of file
@@ -0,0 +1,416 @@
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::I2c;
+
+use crate::measurement::SensorId;
+use crate::protocols::i2c::{AsyncI2CWrapper, I2CError};
+
+use super::calibration::{self, Calibration};
+use super::constants::{registers, values};
+use super::fields::{self, FieldWrite};
+use super::{BME280Config, BME280Error, BME280Measurement, Channel, Compensation, Filter, Mode, Oversampling, RawData, StandyTime, Variant};
+
+// Async counterpart of `BME280`, sharing its register layout and compensation.
+pub struct BME280Async<I2C> {
+    dev: AsyncI2CWrapper<I2C>,
+    calibration: Calibration,
+    config: Option<BME280Config>,
+    compensation: Compensation,
+    variant: Variant
+}
+
+impl<I2C: I2c> BME280Async<I2C> {
+
+    pub async fn new(dev: I2C, address: u8) -> Result<BME280Async<I2C>, BME280Error<I2C::Error>> {
+        let mut wrapper = AsyncI2CWrapper::new(dev, address);
+        let mut id = [0u8];
+        wrapper.read_from_register(registers::CHIP_ID_REG, &mut id).await?;
+        let variant = super::driver_variant(id[0])?;
+        let calibration = read_calibration(&mut wrapper, variant.has_humidity()).await?;
+        Ok(BME280Async { dev: wrapper, calibration, config: None, compensation: Compensation::default(), variant })
+    }
+
+    pub fn id(&self) -> SensorId {
+        SensorId::new(self.variant.name(), self.dev.address())
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub async fn build(dev: I2C, address: u8) -> Result<BME280Async<I2C>, BME280Error<I2C::Error>> {
+        let mut sensor = BME280Async::new(dev, address).await?;
+        sensor.start().await?;
+        Ok(sensor)
+    }
+
+    // Start all parameters from for the sensor
+    pub async fn start(&mut self) -> Result<(), BME280Error<I2C::Error>> {
+        self.configure(BME280Config::default_for(self.variant)).await
+    }
+
+    // Same sequence as `BME280::configure`
+    pub async fn configure(&mut self, config: BME280Config) -> Result<(), BME280Error<I2C::Error>> {
+        super::check_humidity_config(self.variant, &config)?;
+        let standby = super::standby_code(self.variant, config.standby)?;
+        let osrs_t = u8::from(config.osrs_t);
+        let osrs_p = u8::from(config.osrs_p);
+        let mode = match config.mode {
+            Mode::Forced => Mode::Sleep,
+            mode => mode
+        };
+
+        self.dev.write_to_register(registers::CTRL_MEAS_REG, &[fields::ctrl_meas(osrs_t, osrs_p, Mode::Sleep.into())]).await?;
+        if self.variant.has_humidity() {
+            self.write_field(fields::HUMIDITY_OVERSAMPLE, config.osrs_h.into()).await?;
+        }
+        let filter = u8::from(config.filter);
+        self.update_register(registers::CONFIG_REG, |state| fields::with_config(state, standby, filter)).await?;
+        if mode != Mode::Sleep {
+            self.dev.write_to_register(registers::CTRL_MEAS_REG, &[fields::ctrl_meas(osrs_t, osrs_p, mode.into())]).await?;
+        }
+
+        self.config = Some(config);
+        Ok(())
+    }
+
+    pub fn config(&self) -> Option<BME280Config> {
+        self.config
+    }
+
+    pub async fn read_config(&mut self) -> Result<BME280Config, BME280Error<I2C::Error>> {
+        let ctrl_hum = self.read_ctrl_humidity().await?;
+        let ctrl_meas = self.read_register(registers::CTRL_MEAS_REG).await?;
+        let config = self.read_register(registers::CONFIG_REG).await?;
+        super::decode_config(self.variant, ctrl_hum, ctrl_meas, config)
+    }
+
+    pub async fn verify_config(&mut self, expected: &BME280Config) -> Result<(), BME280Error<I2C::Error>> {
+        let found = self.read_config().await?;
+        if !expected.matches(&found) {
+            return Err(BME280Error::ConfigMismatch { expected: *expected, found })
+        }
+        Ok(())
+    }
+
+    pub fn calibration(&self) -> &Calibration {
+        &self.calibration
+    }
+
+    pub fn compensation(&self) -> Compensation {
+        self.compensation
+    }
+
+    pub fn set_compensation(&mut self, compensation: Compensation) {
+        self.compensation = compensation;
+    }
+
+    pub async fn stop(&mut self) -> Result<(), BME280Error<I2C::Error>> {
+        self.set_mode(Mode::Sleep).await
+    }
+
+    pub async fn get_id(&mut self) -> Result<u8, BME280Error<I2C::Error>> {
+        let id = self.read_register(registers::CHIP_ID_REG).await?;
+        if id != u8::from(self.variant) {
+            return Err(BME280Error::ChipIdMismatch { expected: self.variant.into(), found: id })
+        }
+        Ok(id)
+    }
+
+    pub async fn reset<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), BME280Error<I2C::Error>> {
+        self.dev.write_to_register(registers::RST_REG, &[values::SOFT_RESET]).await?;
+        delay.delay_us(super::STARTUP_TIME_US).await;
+        if !self.wait_for_status(delay, fields::updating_bit).await? {
+            return Err(BME280Error::NotReady)
+        }
+
+        self.get_id().await?;
+        self.calibration = read_calibration(&mut self.dev, self.variant.has_humidity()).await?;
+        if let Some(config) = self.config {
+            self.configure(config).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_mode(&mut self) -> Result<Mode, BME280Error<I2C::Error>> {
+        super::decode_mode(self.read_register(registers::CTRL_MEAS_REG).await?)
+    }
+
+    pub async fn set_mode(&mut self, mode: Mode) -> Result<(), BME280Error<I2C::Error>> {
+        self.write_mode(mode).await?;
+        self.update_config(|config| config.mode = mode);
+        Ok(())
+    }
+
+    pub async fn is_measuring(&mut self) -> Result<bool, BME280Error<I2C::Error>> {
+        let status = self.read_register(registers::STAT_REG).await?;
+        Ok(fields::measuring_bit(status) == 1)
+    }
+
+    pub async fn is_updating(&mut self) -> Result<bool, BME280Error<I2C::Error>> {
+        let status = self.read_register(registers::STAT_REG).await?;
+        Ok(fields::updating_bit(status) == 1)
+    }
+
+    pub async fn set_humidity_oversample(&mut self, rate: Oversampling) -> Result<(), BME280Error<I2C::Error>> {
+        super::require_humidity(self.variant)?;
+        let device_mode = self.get_mode().await?;
+        self.write_mode(Mode::Sleep).await?;
+        self.write_field(fields::HUMIDITY_OVERSAMPLE, u8::from(rate)).await?;
+        self.write_mode(device_mode).await?;
+        self.update_config(|config| config.osrs_h = rate);
+        Ok(())
+    }
+
+    pub async fn set_temperature_oversample(&mut self, rate: Oversampling) -> Result<(), BME280Error<I2C::Error>> {
+        let device_mode = self.get_mode().await?;
+        self.write_mode(Mode::Sleep).await?;
+        self.write_field(fields::TEMPERATURE_OVERSAMPLE, u8::from(rate)).await?;
+        self.write_mode(device_mode).await?;
+        self.update_config(|config| config.osrs_t = rate);
+        Ok(())
+    }
+
+    pub async fn set_pressure_oversample(&mut self, rate: Oversampling) -> Result<(), BME280Error<I2C::Error>> {
+        let device_mode = self.get_mode().await?;
+        self.write_mode(Mode::Sleep).await?;
+        self.write_field(fields::PRESSURE_OVERSAMPLE, u8::from(rate)).await?;
+        self.write_mode(device_mode).await?;
+        self.update_config(|config| config.osrs_p = rate);
+        Ok(())
+    }
+
+    pub async fn get_humidity_oversample(&mut self) -> Result<Oversampling, BME280Error<I2C::Error>> {
+        super::require_humidity(self.variant)?;
+        Ok(fields::humidity_oversample(self.read_register(registers::CTRL_HUMIDITY_REG).await?).into())
+    }
+
+    pub async fn get_temperature_oversample(&mut self) -> Result<Oversampling, BME280Error<I2C::Error>> {
+        Ok(fields::temperature_oversample(self.read_register(registers::CTRL_MEAS_REG).await?).into())
+    }
+
+    pub async fn get_pressure_oversample(&mut self) -> Result<Oversampling, BME280Error<I2C::Error>> {
+        Ok(fields::pressure_oversample(self.read_register(registers::CTRL_MEAS_REG).await?).into())
+    }
+
+    pub async fn get_standby_time(&mut self) -> Result<StandyTime, BME280Error<I2C::Error>> {
+        super::decode_standby_time(self.variant, self.read_register(registers::CONFIG_REG).await?)
+    }
+
+    pub async fn get_filter(&mut self) -> Result<Filter, BME280Error<I2C::Error>> {
+        Ok(fields::filter(self.read_register(registers::CONFIG_REG).await?).into())
+    }
+
+    pub async fn set_standby_time(&mut self, standby: StandyTime) -> Result<(), BME280Error<I2C::Error>> {
+        self.write_field(fields::STANDBY_TIME, super::standby_code(self.variant, standby)?).await?;
+        self.update_config(|config| config.standby = standby);
+        Ok(())
+    }
+
+    pub async fn set_filter(&mut self, filter: Filter) -> Result<(), BME280Error<I2C::Error>> {
+        self.write_field(fields::FILTER, u8::from(filter)).await?;
+        self.update_config(|config| config.filter = filter);
+        Ok(())
+    }
+
+    pub async fn measure(&mut self) -> Result<BME280Measurement, BME280Error<I2C::Error>> {
+        let raw = self.read_raw().await?;
+        Ok(self.calibration.compensate(raw, self.compensation))
+    }
+
+    pub async fn read_raw(&mut self) -> Result<RawData, BME280Error<I2C::Error>> {
+        let mut block = fields::SKIPPED_BLOCK;
+        let len = if self.variant.has_humidity() { fields::DATA_BLOCK_LEN } else { fields::PT_BLOCK_LEN };
+        self.dev.read_from_register(registers::PRESSURE_MSB_REG, &mut block[..len]).await?;
+        Ok(fields::raw_data(&block))
+    }
+
+    pub async fn measure_forced<D: DelayNs>(&mut self, delay: &mut D) -> Result<BME280Measurement, BME280Error<I2C::Error>> {
+        let ctrl_hum = self.read_ctrl_humidity().await?;
+        let ctrl_meas = self.read_register(registers::CTRL_MEAS_REG).await?;
+        self.dev.write_to_register(registers::CTRL_MEAS_REG, &[fields::with_mode(ctrl_meas, Mode::Forced.into())]).await?;
+
+        delay.delay_us(super::forced_wait_us(ctrl_meas, ctrl_hum)).await;
+        if !self.wait_for_status(delay, fields::measuring_bit).await? {
+            self.write_mode(Mode::Sleep).await?;
+            return Err(BME280Error::NotReady)
+        }
+
+        self.measure().await
+    }
+
+    pub async fn get_temperature_celsius(&mut self) -> Result<f64, BME280Error<I2C::Error>> {
+        self.measure().await?.get(Channel::Temperature).map_err(BME280Error::ChannelSkipped)
+    }
+
+    pub async fn get_pressure_pascal(&mut self) -> Result<f64, BME280Error<I2C::Error>> {
+        self.measure().await?.get(Channel::Pressure).map_err(BME280Error::ChannelSkipped)
+    }
+
+    pub async fn get_humidity_relative(&mut self) -> Result<f64, BME280Error<I2C::Error>> {
+        super::require_humidity(self.variant)?;
+        self.measure().await?.get(Channel::Humidity).map_err(BME280Error::ChannelSkipped)
+    }
+
+    async fn wait_for_status<D: DelayNs>(&mut self, delay: &mut D, bit: fn(u8) -> u8) -> Result<bool, BME280Error<I2C::Error>> {
+        for _ in 0..super::STATUS_POLL_LIMIT {
+            if bit(self.read_register(registers::STAT_REG).await?) == 0 {
+                return Ok(true)
+            }
+            delay.delay_us(super::STATUS_POLL_INTERVAL_US).await;
+        }
+        Ok(bit(self.read_register(registers::STAT_REG).await?) == 0)
+    }
+
+    async fn write_mode(&mut self, mode: Mode) -> Result<(), BME280Error<I2C::Error>> {
+        self.write_field(fields::MODE, mode.into()).await
+    }
+
+    fn update_config(&mut self, update: impl FnOnce(&mut BME280Config)) {
+        if let Some(config) = self.config.as_mut() {
+            update(config);
+        }
+    }
+
+    async fn read_ctrl_humidity(&mut self) -> Result<u8, BME280Error<I2C::Error>> {
+        if !self.variant.has_humidity() {
+            return Ok(0)
+        }
+        self.read_register(registers::CTRL_HUMIDITY_REG).await
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, BME280Error<I2C::Error>> {
+        let mut buffer = [0u8];
+        self.dev.read_from_register(register, &mut buffer).await?;
+        Ok(buffer[0])
+    }
+
+    async fn write_field(&mut self, field: FieldWrite, value: u8) -> Result<(), BME280Error<I2C::Error>> {
+        self.update_register(field.register, |state| (field.merge)(state, value)).await
+    }
+
+    async fn update_register(&mut self, register: u8, update: impl FnOnce(u8) -> u8) -> Result<(), BME280Error<I2C::Error>> {
+        let old_state = self.read_register(register).await?;
+        self.dev.write_to_register(register, &[update(old_state)]).await?;
+        Ok(())
+    }
+}
+
+async fn read_calibration<I2C: I2c>(dev: &mut AsyncI2CWrapper<I2C>, humidity: bool) -> Result<Calibration, I2CError<I2C::Error>> {
+    let mut tp_block = [0u8; calibration::TP_BLOCK_LEN];
+    let mut h_block = [0u8; calibration::H_BLOCK_LEN];
+    dev.read_from_register(registers::DIG_T1_LSB_REG, &mut tp_block).await?;
+    if humidity {
+        dev.read_from_register(registers::DIG_H2_LSB_REG, &mut h_block).await?;
+    }
+
+    Ok(Calibration::from_blocks(&tp_block, humidity.then_some(&h_block)))
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use super::super::tests::get_mock_calibration;
+    use super::super::{Address, BME280};
+    use super::*;
+
+    fn data_expectations(address: u8) -> Vec<I2cTransaction> {
+        // Three getters, three bursts
+        let data = vec![82, 79, 0, 128, 189, 0, 117, 97];
+        (0..3).map(|_| I2cTransaction::write_read(address, vec![registers::PRESSURE_MSB_REG], data.clone())).collect()
+    }
+
+    #[test]
+    fn matches_blocking_driver() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.extend(data_expectations(address));
+
+        let mut blocking_i2c = I2cMock::new(&expectations);
+        let mut blocking = BME280::new(blocking_i2c.clone(), address).unwrap();
+        let expected = (
+            blocking.get_temperature_celsius().unwrap(),
+            blocking.get_pressure_pascal().unwrap(),
+            blocking.get_humidity_relative().unwrap(),
+        );
+
+        let mut async_i2c = I2cMock::new(&expectations);
+        let actual = block_on(async {
+            let mut sensor = BME280Async::new(async_i2c.clone(), address).await.unwrap();
+            (
+                sensor.get_temperature_celsius().await.unwrap(),
+                sensor.get_pressure_pascal().await.unwrap(),
+                sensor.get_humidity_relative().await.unwrap(),
+            )
+        });
+
+        assert_eq!(actual, expected);
+        blocking_i2c.done();
+        async_i2c.done();
+    }
+
+    #[test]
+    fn measure_forced_async() {
+        use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.extend([
+            I2cTransaction::write_read(address, vec![registers::CTRL_HUMIDITY_REG], vec![0x01]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x24]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x25]),
+            I2cTransaction::write_read(address, vec![registers::STAT_REG], vec![0x00]),
+            I2cTransaction::write_read(address, vec![registers::PRESSURE_MSB_REG], vec![82, 79, 0, 128, 189, 0, 117, 97]),
+        ]);
+
+        let mut i2c = I2cMock::new(&expectations);
+        let mut delay = CheckedDelay::new(&[DelayTransaction::async_delay_us(9300)]);
+        let measurement = block_on(async {
+            let mut sensor = BME280Async::new(i2c.clone(), address).await.unwrap();
+            sensor.measure_forced(&mut delay).await.unwrap()
+        });
+
+        assert_eq!(measurement.temperature, Some(22.81));
+        i2c.done();
+        delay.done();
+    }
+
+    #[test]
+    fn set_mode_keeps_oversampling() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.extend([
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x24]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x27]),
+        ]);
+
+        let mut i2c = I2cMock::new(&expectations);
+        block_on(async {
+            let mut sensor = BME280Async::new(i2c.clone(), address).await.unwrap();
+            sensor.set_mode(Mode::Normal).await.unwrap();
+        });
+        i2c.done();
+    }
+
+    #[test]
+    fn bmp280_standby_times_async() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::CHIP_ID_REG], vec![0x58]),
+            I2cTransaction::write_read(address, vec![registers::DIG_T1_LSB_REG], super::super::tests::MOCK_TP_BLOCK.to_vec()),
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0xE0]),
+        ];
+
+        let mut i2c = I2cMock::new(&expectations);
+        block_on(async {
+            let mut sensor = BME280Async::new(i2c.clone(), address).await.unwrap();
+            assert_eq!(sensor.get_standby_time().await.unwrap(), StandyTime::Ms4000);
+            assert!(matches!(
+                sensor.set_standby_time(StandyTime::Ms20).await,
+                Err(BME280Error::StandbyUnsupported(StandyTime::Ms20))
+            ));
+        });
+        i2c.done();
+    }
+}
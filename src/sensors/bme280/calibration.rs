@@ -1,18 +1,27 @@
 #![allow(unused_imports)]
 use byteorder::{LittleEndian, BigEndian, ByteOrder};
-use embedded_hal::blocking::i2c::{Write, WriteRead};
 
-use crate::protocols::i2c::I2CWrapper;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use crate::sensors::bme280::constants::registers;
+use crate::sensors::bme280::{BME280Measurement, Compensation, RawData};
+
+// Calibration registers 0x88 to 0xA1 (0xA0 is unused)
+pub const TP_BLOCK_LEN: usize = (registers::DIG_H1_REG - registers::DIG_T1_LSB_REG + 1) as usize;
+// Calibration registers 0xE1 to 0xE7
+pub const H_BLOCK_LEN: usize = (registers::DIG_H6_REG - registers::DIG_H2_LSB_REG + 1) as usize;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Calibration {
     pub temperature: TemperatureCalibration,
     pub pressure: PressureCalibration,
-    pub humidity: HumidityCalibration,
+    pub humidity: Option<HumidityCalibration>,  // None on the BMP280
 }
 
 impl Calibration {
-    pub fn new(temperature: TemperatureCalibration, pressure: PressureCalibration, humidity: HumidityCalibration) -> Calibration {
+    pub fn new(temperature: TemperatureCalibration, pressure: PressureCalibration, humidity: Option<HumidityCalibration>) -> Calibration {
         Calibration{
             temperature,
             pressure,
@@ -20,176 +29,140 @@ impl Calibration {
         }
     }
 
-    pub fn build<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> Calibration {
+    // Decode the two calibration blocks as laid out in the datasheet (table 16),
+    // the BMP280 has no humidity block. The drivers only read the blocks.
+    pub fn from_blocks(tp_block: &[u8; TP_BLOCK_LEN], h_block: Option<&[u8; H_BLOCK_LEN]>) -> Calibration {
         Self::new(
-            TemperatureCalibration::build(dev),
-            PressureCalibration::build(dev),
-            HumidityCalibration::build(dev)
+            TemperatureCalibration::parse(tp_block),
+            PressureCalibration::parse(tp_block),
+            h_block.map(|h_block| HumidityCalibration::parse(tp_block, h_block))
         )
     }
+
+    // Compensate raw ADC values, on the sensor or offline from a log. Skipped
+    // channels come back as None, and so do pressure and humidity when
+    // temperature is skipped since both need its t_fine.
+    pub fn compensate(&self, raw: RawData, compensation: Compensation) -> BME280Measurement {
+        let Some(adc_t) = raw.adc_t() else {
+            return BME280Measurement::default()
+        };
+
+        if compensation == Compensation::Float {
+            let (temperature, t_fine) = self.temperature.compensate_temperature_f64(adc_t);
+            return BME280Measurement {
+                temperature: Some(temperature),
+                pressure: raw.adc_p().map(|adc_p| self.pressure.compensate_pressure_f64(adc_p, t_fine)),
+                humidity: raw.adc_h().zip(self.humidity).map(|(adc_h, humidity)| humidity.compensate_humidity_f64(adc_h, t_fine))
+            }
+        }
+
+        let t_fine = self.temperature.compensate_temperature(adc_t);
+        let pressure = raw.adc_p().map(|adc_p| match compensation {
+            Compensation::Int32 => f64::from(self.pressure.compensate_pressure_i32(adc_p, t_fine)),
+            _ => pressure_pascal(self.pressure.compensate_pressure(adc_p, t_fine))
+        });
+
+        BME280Measurement {
+            temperature: Some(temperature_celsius(t_fine)),
+            pressure,
+            humidity: raw.adc_h().zip(self.humidity).map(|(adc_h, humidity)| humidity_relative(humidity.compensate_humidity(adc_h, t_fine)))
+        }
+    }
+}
+
+// Fixed point outputs of the compensation formulas converted to units.
+fn temperature_celsius(t_fine: i32) -> f64 {
+    let output = (t_fine * 5 + 128) >> 8;
+    f64::from(output) / 100.0
+}
+
+fn pressure_pascal(pressure: u32) -> f64 {
+    f64::from(pressure) / 256.0
+}
+
+fn humidity_relative(humidity: u32) -> f64 {
+    f64::from(humidity) / 1024.0
 }
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TemperatureCalibration {
-    t1: u16,
-    t2: i16,
-    t3: i16
+    pub t1: u16,
+    pub t2: i16,
+    pub t3: i16
 }
 
 impl TemperatureCalibration {
-    fn new(t1: u16, t2: i16, t3: i16) -> TemperatureCalibration {
+    pub fn new(t1: u16, t2: i16, t3: i16) -> TemperatureCalibration {
         TemperatureCalibration{t1,t2,t3}
     }
 
-    fn build<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> TemperatureCalibration {
+    fn parse(tp_block: &[u8; TP_BLOCK_LEN]) -> TemperatureCalibration {
         Self::new(
-            Self::read_register_t1(dev),
-            Self::read_register_t2(dev),
-            Self::read_register_t3(dev)
+            LittleEndian::read_u16(&tp_block[0..2]),
+            LittleEndian::read_i16(&tp_block[2..4]),
+            LittleEndian::read_i16(&tp_block[4..6])
         )
     }
 
-    fn read_register_t1<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_T1_LSB_REG,
-            registers::DIG_T1_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_u16(&buffer)
-    }
-
-    fn read_register_t2<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_T2_LSB_REG,
-            registers::DIG_T2_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_i16(&buffer)
-    }
-
-    fn read_register_t3<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_T3_LSB_REG,
-            registers::DIG_T3_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_i16(&buffer)
-    }
-
-    pub fn compensate_temperature(self: &Self, adc_t: i32) -> i32 {
-        let var1 = ((((adc_t>>3) - (i32::from(self.t1)<<1))) * (i32::from(self.t2))) >> 11;
+    pub fn compensate_temperature(&self, adc_t: i32) -> i32 {
+        let var1 = (((adc_t>>3) - (i32::from(self.t1)<<1)) * (i32::from(self.t2))) >> 11;
         let var2 = (((((adc_t>>4) - i32::from(self.t1)) * ((adc_t>>4) - i32::from(self.t1))) >> 12) * i32::from(self.t3)) >> 14;
         var1 + var2
     }
+
+    // Double precision formula (datasheet section 8.1), returns °C and t_fine
+    pub fn compensate_temperature_f64(&self, adc_t: i32) -> (f64, i32) {
+        let adc_t = f64::from(adc_t);
+        let t1 = f64::from(self.t1);
+        let var1 = (adc_t / 16384.0 - t1 / 1024.0) * f64::from(self.t2);
+        let var2 = (adc_t / 131072.0 - t1 / 8192.0) * (adc_t / 131072.0 - t1 / 8192.0) * f64::from(self.t3);
+        ((var1 + var2) / 5120.0, (var1 + var2) as i32)
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PressureCalibration {
-    p1: u16,
-    p2: i16,
-    p3: i16,
-    p4: i16,
-    p5: i16,
-    p6: i16,
-    p7: i16,
-    p8: i16,
-    p9: i16
+    pub p1: u16,
+    pub p2: i16,
+    pub p3: i16,
+    pub p4: i16,
+    pub p5: i16,
+    pub p6: i16,
+    pub p7: i16,
+    pub p8: i16,
+    pub p9: i16
 }
 
 impl PressureCalibration {
-    fn new(p1: u16, p2: i16, p3: i16, p4: i16, p5: i16, p6: i16, p7: i16, p8: i16, p9: i16) -> PressureCalibration {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(p1: u16, p2: i16, p3: i16, p4: i16, p5: i16, p6: i16, p7: i16, p8: i16, p9: i16) -> PressureCalibration {
         PressureCalibration{p1,p2,p3,p4,p5,p6,p7,p8,p9}
     }
 
-    fn build<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> PressureCalibration {
+    fn parse(tp_block: &[u8; TP_BLOCK_LEN]) -> PressureCalibration {
         Self::new(
-            Self::read_register_p1(dev),
-            Self::read_register_p2(dev),
-            Self::read_register_p3(dev),
-            Self::read_register_p4(dev),
-            Self::read_register_p5(dev),
-            Self::read_register_p6(dev),
-            Self::read_register_p7(dev),
-            Self::read_register_p8(dev),
-            Self::read_register_p9(dev)
+            LittleEndian::read_u16(&tp_block[6..8]),
+            LittleEndian::read_i16(&tp_block[8..10]),
+            LittleEndian::read_i16(&tp_block[10..12]),
+            LittleEndian::read_i16(&tp_block[12..14]),
+            LittleEndian::read_i16(&tp_block[14..16]),
+            LittleEndian::read_i16(&tp_block[16..18]),
+            LittleEndian::read_i16(&tp_block[18..20]),
+            LittleEndian::read_i16(&tp_block[20..22]),
+            LittleEndian::read_i16(&tp_block[22..24])
         )
     }
 
-    fn read_register_p1<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_P1_LSB_REG,
-            registers::DIG_P1_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_u16(&buffer)
-    }
-
-    fn read_register_p2<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_P2_LSB_REG,
-            registers::DIG_P2_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_i16(&buffer)
-    }
-
-    fn read_register_p3<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_P3_LSB_REG,
-            registers::DIG_P3_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_i16(&buffer)
-    }
-
-    fn read_register_p4<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_P4_LSB_REG,
-            registers::DIG_P4_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_i16(&buffer)
-    }
-
-    fn read_register_p5<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_P5_LSB_REG,
-            registers::DIG_P5_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_i16(&buffer)
-    }
-
-    fn read_register_p6<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_P6_LSB_REG,
-            registers::DIG_P6_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_i16(&buffer)
-    }
-
-    fn read_register_p7<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_P7_LSB_REG,
-            registers::DIG_P7_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_i16(&buffer)
-    }
-
-    fn read_register_p8<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_P8_LSB_REG,
-            registers::DIG_P8_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_i16(&buffer)
-    }
-
-    fn read_register_p9<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let buffer = read_multiple_registers(dev, &[
-            registers::DIG_P9_LSB_REG,
-            registers::DIG_P9_MSB_REG
-        ]).unwrap();
-        LittleEndian::read_i16(&buffer)
-    }
-
-    pub fn compensate_pressure(self: &Self, adc_p: i32, t_fine: i32) -> u32 {
+    pub fn compensate_pressure(&self, adc_p: i32, t_fine: i32) -> u32 {
         let var1 = i64::from(t_fine) - 128000;
         let var2 = var1 * var1 * i64::from(self.p6);
         let var2 = var2 + ((var1 * i64::from(self.p5)) << 17);
         let var2 = var2 + (i64::from(self.p4) << 35);
         let var1 = ((var1 * var1 * i64::from(self.p3)) >> 8) + ((var1 * i64::from(self.p2)) << 12);
-        let var1 = ((1_i64 << 47) + var1) * i64::from(self.p1) >> 33;
+        let var1 = (((1_i64 << 47) + var1) * i64::from(self.p1)) >> 33;
 
         if var1 == 0 {
             0
@@ -200,19 +173,62 @@ impl PressureCalibration {
             let var2 = ((i64::from(self.p8)) * p) >> 19;
             let p = ((p + var1 + var2) >> 8) + ((i64::from(self.p7)) << 4);
     
-            let output = p as u32;
-            output    
+            p as u32
         }
     }
+
+    // 32 bit formula (datasheet section 8.2), returns Pa
+    pub fn compensate_pressure_i32(&self, adc_p: i32, t_fine: i32) -> u32 {
+        let var1 = (t_fine >> 1) - 64000;
+        let var2 = (((var1 >> 2) * (var1 >> 2)) >> 11) * i32::from(self.p6);
+        let var2 = var2 + ((var1 * i32::from(self.p5)) << 1);
+        let var2 = (var2 >> 2) + (i32::from(self.p4) << 16);
+        let var1 = (((i32::from(self.p3) * (((var1 >> 2) * (var1 >> 2)) >> 13)) >> 3) + ((i32::from(self.p2) * var1) >> 1)) >> 18;
+        let var1 = ((32768 + var1) * i32::from(self.p1)) >> 15;
+
+        if var1 == 0 {
+            return 0
+        }
+        let p = (((1_048_576 - adc_p) - (var2 >> 12)) as u32).wrapping_mul(3125);
+        let p = if p < 0x8000_0000 {
+            (p << 1) / var1 as u32
+        } else {
+            (p / var1 as u32) * 2
+        };
+        let var1 = (i32::from(self.p9) * ((((p >> 3) * (p >> 3)) >> 13) as i32)) >> 12;
+        let var2 = (((p >> 2) as i32) * i32::from(self.p8)) >> 13;
+        (p as i32 + ((var1 + var2 + i32::from(self.p7)) >> 4)) as u32
+    }
+
+    // Double precision formula (datasheet section 8.1), returns Pa
+    pub fn compensate_pressure_f64(&self, adc_p: i32, t_fine: i32) -> f64 {
+        let var1 = f64::from(t_fine) / 2.0 - 64000.0;
+        let var2 = var1 * var1 * f64::from(self.p6) / 32768.0;
+        let var2 = var2 + var1 * f64::from(self.p5) * 2.0;
+        let var2 = var2 / 4.0 + f64::from(self.p4) * 65536.0;
+        let var1 = (f64::from(self.p3) * var1 * var1 / 524288.0 + f64::from(self.p2) * var1) / 524288.0;
+        let var1 = (1.0 + var1 / 32768.0) * f64::from(self.p1);
+
+        if var1 == 0.0 {
+            return 0.0
+        }
+        let p = 1_048_576.0 - f64::from(adc_p);
+        let p = (p - var2 / 4096.0) * 6250.0 / var1;
+        let var1 = f64::from(self.p9) * p * p / 2147483648.0;
+        let var2 = p * f64::from(self.p8) / 32768.0;
+        p + (var1 + var2 + f64::from(self.p7)) / 16.0
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HumidityCalibration {
-    h1: u8,
-    h2: i16,
-    h3: u8,
-    h4: i16,
-    h5: i16,
-    h6: i8
+    pub h1: u8,
+    pub h2: i16,
+    pub h3: u8,
+    pub h4: i16,
+    pub h5: i16,
+    pub h6: i8
 }
 
 impl HumidityCalibration {
@@ -220,85 +236,40 @@ impl HumidityCalibration {
         HumidityCalibration{h1,h2,h3,h4,h5,h6}
     }
 
-    pub fn build<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> HumidityCalibration {
+    fn parse(tp_block: &[u8; TP_BLOCK_LEN], h_block: &[u8; H_BLOCK_LEN]) -> HumidityCalibration {
+        // H4 and H5 are signed 12 bit values sharing the nibbles of 0xE5
+        let h4 = (i16::from(h_block[3] as i8) << 4) | i16::from(h_block[4] & 0x0F);
+        let h5 = (i16::from(h_block[5] as i8) << 4) | i16::from(h_block[4] >> 4);
+
         Self::new(
-            Self::read_register_h1(dev),
-            Self::read_register_h2(dev),
-            Self::read_register_h3(dev),
-            Self::read_register_h4(dev),
-            Self::read_register_h5(dev),
-            Self::read_register_h6(dev)
+            tp_block[TP_BLOCK_LEN - 1],
+            LittleEndian::read_i16(&h_block[0..2]),
+            h_block[2],
+            h4,
+            h5,
+            h_block[6] as i8
         )
     }
 
-    fn read_register_h1<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u8 {
-        let mut buffer = read_multiple_registers(dev, &[registers::DIG_H1_REG]).unwrap();
-        buffer.pop().unwrap()
-    }
-
-    fn read_register_h2<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let buffer: Vec<u8> = read_multiple_registers(dev, &[
-            registers::DIG_H2_LSB_REG,
-            registers::DIG_H2_MSB_REG,
-        ]).unwrap();
-        LittleEndian::read_i16(&buffer)
-    }
-
-    fn read_register_h3<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u8 {
-        let mut buffer = read_multiple_registers(dev, &[registers::DIG_H3_REG]).unwrap();
-        buffer.pop().unwrap()
-    }
-
-    fn read_register_h4<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let mut buffer  = [0u8; 2];
-        dev.read_from_register(registers::DIG_H4_MSB_REG, &mut buffer[0..1]).unwrap();
-        dev.read_from_register(registers::DIG_H4_LSB_REG, &mut buffer[1..2]).unwrap();
-
-        ((u16::from(buffer[0]) << 4) | (u16::from(buffer[1]) & 0x0F)) as i16
-    }
-
-    fn read_register_h5<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i16 {
-        let mut buffer  = [0u8; 2];
-        dev.read_from_register(registers::DIG_H5_MSB_REG, &mut buffer[0..1]).unwrap();
-        dev.read_from_register(registers::DIG_H4_LSB_REG, &mut buffer[1..2]).unwrap();
-
-        (((u16::from(buffer[0]) << 4)) | ((u16::from(buffer[1]) >> 4) & 0x0F)) as i16
-    }
-
-    fn read_register_h6<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> i8 {
-        let mut buffer  = [0u8; 1];
-        dev.read_from_register(registers::DIG_H6_REG, &mut buffer).unwrap();
-
-        buffer[0] as i8
-    }
-
-    pub fn compensate_humidity(self: &Self, adc_h: i32, t_fine: i32) -> u32 {
+    pub fn compensate_humidity(&self, adc_h: i32, t_fine: i32) -> u32 {
         let mut var1 = t_fine - 76800i32;
         var1 = ((((adc_h << 14) - (i32::from(self.h4) << 20) - (i32::from(self.h5) * var1)) +
             (16384)) >> 15) * (((((((var1 * i32::from(self.h6)) >> 10) * (((var1 * i32::from(self.h3)) >> 11) + (32768))) >> 10) + (2097152)) *
             i32::from(self.h2) + 8192) >> 14);
         var1 = var1 - (((((var1 >> 15) * (var1 >> 15)) >> 7) * i32::from(self.h1)) >> 4);
-        if var1 < 0 {
-            var1 = 0;
-        } else if var1 > 419430400 {
-            var1 = 419430400;
-        }
+        var1 = var1.clamp(0, 419430400);
 
         (var1 >> 12) as u32
     }
-}
 
-
-fn read_multiple_registers<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>, registers: &[u8]) -> Result<Vec<u8>, ()> {
-    let mut buffer: Vec<u8> = vec![];
-    for register in registers.iter() {
-        let mut temp_buffer  = [0u8];
-        match dev.read_from_register(*register, &mut temp_buffer) {
-            Ok(_) => buffer.extend(temp_buffer),
-            Err(_) => {return Err(())}
-        }
+    // Double precision formula (datasheet section 8.1), returns %RH
+    pub fn compensate_humidity_f64(&self, adc_h: i32, t_fine: i32) -> f64 {
+        let var_h = f64::from(t_fine) - 76800.0;
+        let var_h = (f64::from(adc_h) - (f64::from(self.h4) * 64.0 + f64::from(self.h5) / 16384.0 * var_h)) *
+            (f64::from(self.h2) / 65536.0 * (1.0 + f64::from(self.h6) / 67108864.0 * var_h * (1.0 + f64::from(self.h3) / 67108864.0 * var_h)));
+        let var_h = var_h * (1.0 - f64::from(self.h1) * var_h / 524288.0);
+        var_h.clamp(0.0, 100.0)
     }
-    Ok(buffer)
 }
 
 
@@ -355,6 +326,35 @@ mod tests {
         )
     }
 
+    #[test]
+    fn parse_signed_humidity_coefficients() {
+        let tp_block = [0u8; TP_BLOCK_LEN];
+        // H4 = -2 (0xFE << 4 | 0xE), H5 = -3 (0xFF << 4 | 0xD)
+        let h_block = [0x6D, 0x01, 0x00, 0xFF, 0xDE, 0xFF, 0x1E];
+        let h_cal = HumidityCalibration::parse(&tp_block, &h_block);
+
+        assert_eq!(h_cal.h2, 365);
+        assert_eq!(h_cal.h4, -2);
+        assert_eq!(h_cal.h5, -3);
+        assert_eq!(h_cal.h6, 30);
+    }
+
+    #[test]
+    fn calibration_from_blocks() {
+        let mut tp_block = [0u8; TP_BLOCK_LEN];
+        // T1 = 27504, little endian
+        tp_block[0..2].copy_from_slice(&[0x70, 0x6B]);
+        tp_block[TP_BLOCK_LEN - 1] = 75;  // H1
+        let h_block = [0x6D, 0x01, 0x00, 0xFF, 0xDE, 0xFF, 0x1E];
+
+        let bme280 = Calibration::from_blocks(&tp_block, Some(&h_block));
+        assert_eq!(bme280.temperature.t1, 27504);
+        assert_eq!(bme280.humidity.map(|humidity| (humidity.h1, humidity.h2)), Some((75, 365)));
+
+        let bmp280 = Calibration::from_blocks(&tp_block, None);
+        assert_eq!((bmp280.temperature, bmp280.pressure, bmp280.humidity), (bme280.temperature, bme280.pressure, None));
+    }
+
     #[test]
     fn humidity_calibration_test() {
         let h_cal = create_humidity_calibration();
@@ -363,4 +363,102 @@ mod tests {
         assert_eq!(h_comp, 57350)
     }
 
+    // Worked example from the BMP280 datasheet (section 3.12), whose
+    // temperature and pressure formulas the BME280 shares.
+    const DATASHEET_ADC_T: i32 = 519888;
+    const DATASHEET_ADC_P: i32 = 415148;
+
+    fn datasheet_calibration() -> (TemperatureCalibration, PressureCalibration) {
+        (
+            TemperatureCalibration::new(27504, 26435, -1000),
+            PressureCalibration::new(36477, -10685, 3024, 2855, 140, -7, 15500, -14600, 6000)
+        )
+    }
+
+    #[test]
+    fn datasheet_reference_values() {
+        let (t_cal, p_cal) = datasheet_calibration();
+
+        let t_fine = t_cal.compensate_temperature(DATASHEET_ADC_T);
+        assert_eq!(t_fine, 128422);
+        assert_eq!((t_fine * 5 + 128) >> 8, 2508);
+        assert!((f64::from(p_cal.compensate_pressure(DATASHEET_ADC_P, t_fine)) / 256.0 - 100653.27).abs() < 0.05);
+        // The 32 bit formula truncates intermediate terms and lands a few Pa off
+        assert!(p_cal.compensate_pressure_i32(DATASHEET_ADC_P, t_fine).abs_diff(100653) <= 4);
+
+        let (temperature, t_fine) = t_cal.compensate_temperature_f64(DATASHEET_ADC_T);
+        assert_eq!(t_fine, 128422);
+        assert!((temperature - 25.08).abs() < 0.01);
+        assert!((p_cal.compensate_pressure_f64(DATASHEET_ADC_P, t_fine) - 100653.27).abs() < 0.05);
+    }
+
+    fn create_calibration() -> Calibration {
+        Calibration::new(create_temperature_calibration(), create_pressure_calibration(), Some(create_humidity_calibration()))
+    }
+
+    #[test]
+    fn compensate_offline() {
+        let raw = RawData { pressure: 0x524F0, temperature: 0x80BD0, humidity: 0x7561 };
+        let measurement = create_calibration().compensate(raw, Compensation::Int64);
+
+        assert_eq!(measurement.temperature, Some(22.81));
+        assert_eq!(measurement.pressure, Some(101602.51171875));
+        assert_eq!(measurement.humidity, Some(56.005859375));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json_round_trip() {
+        let calibration = create_calibration();
+        let json = serde_json::to_string(&calibration).unwrap();
+
+        assert!(json.contains(r#""temperature":{"t1":28485,"t2":26735,"t3":50}"#));
+        assert_eq!(serde_json::from_str::<Calibration>(&json).unwrap(), calibration);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn toml_round_trip() {
+        let calibration = create_calibration();
+        let text = toml::to_string(&calibration).unwrap();
+
+        assert!(text.contains("[humidity]\nh1 = 75\nh2 = 365\n"));
+        assert_eq!(toml::from_str::<Calibration>(&text).unwrap(), calibration);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn compensate_logged_readings() {
+        let calibration: Calibration = serde_json::from_str(&serde_json::to_string(&create_calibration()).unwrap()).unwrap();
+        let log = r#"[{"pressure":337136,"temperature":527312,"humidity":30049}]"#;
+        let readings: Vec<RawData> = serde_json::from_str(log).unwrap();
+
+        let measurement = calibration.compensate(readings[0], Compensation::Int64);
+        assert_eq!(measurement.temperature, Some(22.81));
+        assert_eq!(measurement.pressure, Some(101602.51171875));
+    }
+
+    #[test]
+    fn backends_agree() {
+        let t_cal = create_temperature_calibration();
+        let p_cal = create_pressure_calibration();
+        let h_cal = create_humidity_calibration();
+        let (adc_t, adc_p, adc_h) = (519888, 415148, 30177);
+
+        let t_fine = t_cal.compensate_temperature(adc_t);
+        let (temperature, float_t_fine) = t_cal.compensate_temperature_f64(adc_t);
+        assert!((f64::from((t_fine * 5 + 128) >> 8) / 100.0 - temperature).abs() < 0.01);
+        assert!((t_fine - float_t_fine).abs() <= 1);
+
+        let int64 = f64::from(p_cal.compensate_pressure(adc_p, t_fine)) / 256.0;
+        let int32 = f64::from(p_cal.compensate_pressure_i32(adc_p, t_fine));
+        let float = p_cal.compensate_pressure_f64(adc_p, t_fine);
+        assert!((int64 - float).abs() < 0.5);
+        assert!((int32 - float).abs() <= 4.0);
+
+        let int = f64::from(h_cal.compensate_humidity(adc_h, t_fine)) / 1024.0;
+        let float = h_cal.compensate_humidity_f64(adc_h, t_fine);
+        assert!((int - float).abs() < 0.01);
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,117 @@
+use super::{Filter, Mode, Oversampling, StandyTime, Variant};
+
+// Full measurement setup, written to the chip in one go by `configure`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BME280Config {
+    pub mode: Mode,
+    pub osrs_t: Oversampling,
+    pub osrs_p: Oversampling,
+    pub osrs_h: Oversampling,
+    pub filter: Filter,
+    pub standby: StandyTime
+}
+
+impl BME280Config {
+    // Whether a configuration read back from the chip is this one. A chip
+    // configured for forced mode idles in sleep between conversions.
+    pub fn matches(&self, found: &BME280Config) -> bool {
+        let mode_matches = self.mode == found.mode || (self.mode == Mode::Forced && found.mode == Mode::Sleep);
+        mode_matches && BME280Config { mode: self.mode, ..*found } == *self
+    }
+
+    // The same setup with humidity skipped, for a BMP280
+    pub fn without_humidity(self) -> BME280Config {
+        BME280Config { osrs_h: Oversampling::Skipped, ..self }
+    }
+
+    // What `start` applies: the default, less what the chip lacks
+    pub fn default_for(variant: Variant) -> BME280Config {
+        if variant.has_humidity() {
+            BME280Config::default()
+        } else {
+            BME280Config::default().without_humidity()
+        }
+    }
+}
+
+// Recommended modes of operation, datasheet section 3.5.
+impl BME280Config {
+    // One forced measurement per minute, no filtering
+    pub fn weather_monitoring() -> BME280Config {
+        BME280Config {
+            mode: Mode::Forced,
+            osrs_t: Oversampling::Ox1,
+            osrs_p: Oversampling::Ox1,
+            osrs_h: Oversampling::Ox1,
+            filter: Filter::Off,
+            standby: StandyTime::Ms1000
+        }
+    }
+
+    // Forced measurements with pressure skipped
+    pub fn humidity_sensing() -> BME280Config {
+        BME280Config {
+            mode: Mode::Forced,
+            osrs_t: Oversampling::Ox1,
+            osrs_p: Oversampling::Skipped,
+            osrs_h: Oversampling::Ox1,
+            filter: Filter::Off,
+            standby: StandyTime::Ms1000
+        }
+    }
+
+    // Continuous low-noise pressure for altitude tracking
+    pub fn indoor_navigation() -> BME280Config {
+        BME280Config {
+            mode: Mode::Normal,
+            osrs_t: Oversampling::Ox2,
+            osrs_p: Oversampling::Ox16,
+            osrs_h: Oversampling::Ox1,
+            filter: Filter::C16,
+            standby: StandyTime::Ms0_5
+        }
+    }
+
+    // Fast pressure updates with humidity skipped
+    pub fn gaming() -> BME280Config {
+        BME280Config {
+            mode: Mode::Normal,
+            osrs_t: Oversampling::Ox1,
+            osrs_p: Oversampling::Ox4,
+            osrs_h: Oversampling::Skipped,
+            filter: Filter::C16,
+            standby: StandyTime::Ms0_5
+        }
+    }
+}
+
+// Continuous measurements of all three channels without filtering, what
+// `BME280::start` applies to a BME280.
+impl Default for BME280Config {
+    fn default() -> Self {
+        BME280Config {
+            mode: Mode::Normal,
+            osrs_t: Oversampling::Ox1,
+            osrs_p: Oversampling::Ox1,
+            osrs_h: Oversampling::Ox1,
+            filter: Filter::Off,
+            standby: StandyTime::Ms0_5
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forced_config_matches_sleeping_chip() {
+        let expected = BME280Config::weather_monitoring();
+
+        assert!(expected.matches(&BME280Config { mode: Mode::Sleep, ..expected }));
+        assert!(!expected.matches(&BME280Config { mode: Mode::Normal, ..expected }));
+        assert!(!expected.matches(&BME280Config { filter: Filter::C2, ..expected }));
+        assert!(!BME280Config::default().matches(&BME280Config { mode: Mode::Sleep, ..Default::default() }));
+    }
+}
@@ -1,6 +1,8 @@
 pub mod values {
     pub const SOFT_RESET: u8 = 0xB6;
     pub const CHIP_ID: u8 = 0x60;
+    pub const BMP280_CHIP_ID: u8 = 0x58;
+    pub const BME680_CHIP_ID: u8 = 0x61;
 }
 
 pub mod registers {
@@ -0,0 +1,188 @@
+// Pure register encoding/decoding shared by the blocking and async drivers.
+// Each `with_*` takes the current register value and returns the new one.
+
+use super::constants::registers;
+
+pub fn mode(ctrl_meas: u8) -> u8 {
+    ctrl_meas & 0x03
+}
+
+pub fn with_mode(ctrl_meas: u8, mode: u8) -> u8 {
+    (ctrl_meas & 0xFC) | mode
+}
+
+pub fn humidity_oversample(ctrl_hum: u8) -> u8 {
+    ctrl_hum & 0x07
+}
+
+pub fn temperature_oversample(ctrl_meas: u8) -> u8 {
+    (ctrl_meas >> 5) & 0x07
+}
+
+pub fn pressure_oversample(ctrl_meas: u8) -> u8 {
+    (ctrl_meas >> 2) & 0x07
+}
+
+pub fn standby_time(config: u8) -> u8 {
+    config >> 5
+}
+
+pub fn filter(config: u8) -> u8 {
+    (config >> 2) & 0x07
+}
+
+pub fn ctrl_meas(osrs_t: u8, osrs_p: u8, mode: u8) -> u8 {
+    (osrs_t << 5) | (osrs_p << 2) | mode
+}
+
+pub fn with_humidity_oversample(ctrl_hum: u8, rate: u8) -> u8 {
+    (ctrl_hum & 0xF8) | rate
+}
+
+pub fn with_temperature_oversample(ctrl_meas: u8, rate: u8) -> u8 {
+    (ctrl_meas & 0x1F) | (rate << 5)
+}
+
+pub fn with_pressure_oversample(ctrl_meas: u8, rate: u8) -> u8 {
+    (ctrl_meas & 0xE3) | (rate << 2)
+}
+
+pub fn with_standby_time(config: u8, standby: u8) -> u8 {
+    (config & 0x1F) | (standby << 5)
+}
+
+pub fn with_filter(config: u8, filter: u8) -> u8 {
+    (config & 0xE3) | (filter << 2)
+}
+
+pub fn with_config(config: u8, standby: u8, filter: u8) -> u8 {
+    with_filter(with_standby_time(config, standby), filter)
+}
+
+// A setting changed by read-modify-write: the register holding it and how a
+// new value merges into its contents. The drivers only do the bus transfers.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldWrite {
+    pub register: u8,
+    pub merge: fn(u8, u8) -> u8
+}
+
+pub const MODE: FieldWrite = FieldWrite { register: registers::CTRL_MEAS_REG, merge: with_mode };
+pub const TEMPERATURE_OVERSAMPLE: FieldWrite = FieldWrite { register: registers::CTRL_MEAS_REG, merge: with_temperature_oversample };
+pub const PRESSURE_OVERSAMPLE: FieldWrite = FieldWrite { register: registers::CTRL_MEAS_REG, merge: with_pressure_oversample };
+pub const HUMIDITY_OVERSAMPLE: FieldWrite = FieldWrite { register: registers::CTRL_HUMIDITY_REG, merge: with_humidity_oversample };
+pub const STANDBY_TIME: FieldWrite = FieldWrite { register: registers::CONFIG_REG, merge: with_standby_time };
+pub const FILTER: FieldWrite = FieldWrite { register: registers::CONFIG_REG, merge: with_filter };
+
+pub fn measuring_bit(status: u8) -> u8 {
+    // Check bit 3 is set to 1
+    (status & 0x08) >> 3
+}
+
+pub fn updating_bit(status: u8) -> u8 {
+    // Check bit 0 is set to 1
+    status & 0x01
+}
+
+// Data registers 0xF7 to 0xFE, read in one burst so all three values come
+// from the same conversion.
+pub const DATA_BLOCK_LEN: usize = 8;
+// The BMP280 stops before the humidity registers
+pub const PT_BLOCK_LEN: usize = 6;
+
+// Uncompensated ADC outputs, small enough to log and compensate later
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RawData {
+    pub pressure: u32,
+    pub temperature: u32,
+    pub humidity: u32
+}
+
+// Values a skipped channel keeps in its data registers (datasheet 5.4.3 and 5.4.5)
+pub const SKIPPED_20BIT: u32 = 0x80000;
+pub const SKIPPED_16BIT: u32 = 0x8000;
+pub const SKIPPED_BLOCK: [u8; DATA_BLOCK_LEN] = [0x80, 0x00, 0x00, 0x80, 0x00, 0x00, 0x80, 0x00];
+
+impl RawData {
+    // Each channel's ADC value, None when it was skipped
+    pub fn adc_t(&self) -> Option<i32> {
+        (self.temperature != SKIPPED_20BIT).then_some(self.temperature as i32)
+    }
+
+    pub fn adc_p(&self) -> Option<i32> {
+        (self.pressure != SKIPPED_20BIT).then_some(self.pressure as i32)
+    }
+
+    pub fn adc_h(&self) -> Option<i32> {
+        (self.humidity != SKIPPED_16BIT).then_some(self.humidity as i32)
+    }
+}
+
+pub fn raw_data(block: &[u8; DATA_BLOCK_LEN]) -> RawData {
+    RawData {
+        pressure: raw_20bit(&[block[0], block[1], block[2]]),
+        temperature: raw_20bit(&[block[3], block[4], block[5]]),
+        humidity: raw_16bit(&[block[6], block[7]])
+    }
+}
+
+// 20 bit value from MSB, LSB and XLSB registers
+pub fn raw_20bit(buffer: &[u8; 3]) -> u32 {
+    (u32::from(buffer[0]) << 12) | (u32::from(buffer[1]) << 4) | ((u32::from(buffer[2]) >> 4) & 0x0F)
+}
+
+// 16 bit value from MSB and LSB registers
+pub fn raw_16bit(buffer: &[u8; 2]) -> u32 {
+    (u32::from(buffer[0]) << 8) | (u32::from(buffer[1]))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_modify_write_preserves_other_bits() {
+        assert_eq!(with_mode(0xFF, 0x00), 0xFC);
+        assert_eq!(with_temperature_oversample(0x03, 0x01), 0x23);
+        assert_eq!(with_pressure_oversample(0x23, 0x01), 0x27);
+        assert_eq!(with_humidity_oversample(0xF0, 0x05), 0xF5);
+        assert_eq!(with_standby_time(0x04, 0x07), 0xE4);
+        assert_eq!(with_filter(0xE0, 0x04), 0xF0);
+        assert_eq!(with_config(0x01, 0x05, 0x02), 0xA9);
+        assert_eq!((TEMPERATURE_OVERSAMPLE.merge)(0x03, 0x01), 0x23);
+    }
+
+    #[test]
+    fn decode_fields() {
+        assert_eq!(temperature_oversample(0x27), 0x01);
+        assert_eq!(pressure_oversample(0x27), 0x01);
+        assert_eq!(humidity_oversample(0xF5), 0x05);
+        assert_eq!(standby_time(0xF1), 0x07);
+        assert_eq!(filter(0xF1), 0x04);
+        assert_eq!(measuring_bit(0x08), 1);
+        assert_eq!(measuring_bit(0x04), 0);
+        assert_eq!(updating_bit(0x09), 1);
+    }
+
+    #[test]
+    fn raw_adc_values() {
+        assert_eq!(raw_20bit(&[0x80, 0xBD, 0x00]), 0x80BD0);
+        assert_eq!(raw_16bit(&[0x75, 0x61]), 0x7561);
+    }
+
+    #[test]
+    fn data_block_layout() {
+        let raw = raw_data(&[0x52, 0x4F, 0x00, 0x80, 0xBD, 0x00, 0x75, 0x61]);
+        assert_eq!(raw, RawData { pressure: 0x524F0, temperature: 0x80BD0, humidity: 0x7561 });
+    }
+
+    #[test]
+    fn skipped_channels() {
+        let raw = raw_data(&[0x80, 0x00, 0x00, 0x80, 0xBD, 0x00, 0x80, 0x00]);
+        assert_eq!(raw.adc_p(), None);
+        assert_eq!(raw.adc_t(), Some(0x80BD0));
+        assert_eq!(raw.adc_h(), None);
+    }
+}
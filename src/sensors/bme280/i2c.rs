@@ -1,112 +1,84 @@
-use embedded_hal::blocking::i2c::{Write, WriteRead};
+use embedded_hal::i2c::I2c;
 
-use crate::{sensors::bme280::constants::{registers, values}, protocols::i2c::I2CWrapper};
+use crate::{sensors::bme280::{calibration::{self, Calibration}, constants::{registers, values}, fields::{self, FieldWrite}}, protocols::i2c::{I2CError, I2CWrapper}};
 
 
-pub fn read_id<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u8 {
-    let mut buffer = [0u8];
-    dev.read_from_register(registers::CHIP_ID_REG, &mut buffer).unwrap();
-    *buffer.first().unwrap()
+pub fn read_id<I2C: I2c>(dev: &mut I2CWrapper<I2C>) -> Result<u8, I2CError<I2C::Error>> {
+    read_register(dev, registers::CHIP_ID_REG)
 }
 
-pub fn write_reset<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) {
-    dev.write_to_register(registers::RST_REG, &[values::SOFT_RESET]).unwrap();
+pub fn write_reset<I2C: I2c>(dev: &mut I2CWrapper<I2C>) -> Result<(), I2CError<I2C::Error>> {
+    dev.write_to_register(registers::RST_REG, &[values::SOFT_RESET])
 }
 
-pub fn read_mode<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u8 {
-    let mut buffer = [0u8];
-    dev.read_from_register(registers::CTRL_MEAS_REG, &mut buffer).unwrap();
-    *buffer.first().unwrap() & 0x03
+pub fn read_mode<I2C: I2c>(dev: &mut I2CWrapper<I2C>) -> Result<u8, I2CError<I2C::Error>> {
+    Ok(fields::mode(read_register(dev, registers::CTRL_MEAS_REG)?))
 }
 
-pub fn write_mode<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>, mode: u8) {
-    let mut buffer = [0u8];
-    dev.read_from_register(registers::CTRL_MEAS_REG, &mut buffer).unwrap();
-    let old_state = *buffer.first().unwrap() & 0xFC;
-    let new_state = old_state | mode;
-    dev.write_to_register(registers::CTRL_MEAS_REG, &[new_state]).unwrap();
+pub fn read_ctrl_meas<I2C: I2c>(dev: &mut I2CWrapper<I2C>) -> Result<u8, I2CError<I2C::Error>> {
+    read_register(dev, registers::CTRL_MEAS_REG)
 }
 
-pub fn read_measuring_bit<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u8 {
-    // Check bit 3 is set to 1
-    (read_status(dev) & 0x04) >> 2
+pub fn write_ctrl_meas<I2C: I2c>(dev: &mut I2CWrapper<I2C>, ctrl_meas: u8) -> Result<(), I2CError<I2C::Error>> {
+    dev.write_to_register(registers::CTRL_MEAS_REG, &[ctrl_meas])
 }
 
-pub fn read_updating_bit<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u8 {
-    // Check bit 0 is set to 1
-    read_status(dev) & 0x01
+pub fn read_ctrl_humidity<I2C: I2c>(dev: &mut I2CWrapper<I2C>) -> Result<u8, I2CError<I2C::Error>> {
+    read_register(dev, registers::CTRL_HUMIDITY_REG)
 }
 
-pub fn read_status<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u8 {
-    let mut buffer = [0u8];
-    dev.read_from_register(registers::STAT_REG, &mut buffer).unwrap();
-    *buffer.first().unwrap()
+pub fn read_config<I2C: I2c>(dev: &mut I2CWrapper<I2C>) -> Result<u8, I2CError<I2C::Error>> {
+    read_register(dev, registers::CONFIG_REG)
 }
 
-pub fn write_humidity_oversample<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>, rate: u8) {
-    let mut buffer = [0u8];
-    dev.read_from_register(registers::CTRL_HUMIDITY_REG, &mut buffer).unwrap();
-
-    let old_state = *buffer.first().unwrap() & 0xF8;
-    let new_state = old_state | rate;
-    dev.write_to_register(registers::CTRL_HUMIDITY_REG, &[new_state]).unwrap();
+pub fn read_measuring_bit<I2C: I2c>(dev: &mut I2CWrapper<I2C>) -> Result<u8, I2CError<I2C::Error>> {
+    Ok(fields::measuring_bit(read_status(dev)?))
 }
 
-pub fn write_temperature_oversample<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>, rate: u8) {
-    let mut buffer = [0u8];
-    dev.read_from_register(registers::CTRL_MEAS_REG, &mut buffer).unwrap();
-
-    let old_state = *buffer.first().unwrap() & 0x1F;
-    let new_state = old_state | (rate << 5);
-    dev.write_to_register(registers::CTRL_MEAS_REG, &[new_state]).unwrap();
+pub fn read_updating_bit<I2C: I2c>(dev: &mut I2CWrapper<I2C>) -> Result<u8, I2CError<I2C::Error>> {
+    Ok(fields::updating_bit(read_status(dev)?))
 }
 
-pub fn write_pressure_oversample<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>, rate: u8) {
-    let mut buffer = [0u8];
-    dev.read_from_register(registers::CTRL_MEAS_REG, &mut buffer).unwrap();
-    let old_state = *buffer.first().unwrap() & 0xE3;
-    let new_state = old_state | (rate << 2);
-    dev.write_to_register(registers::CTRL_MEAS_REG, &[new_state]).unwrap();
+pub fn read_status<I2C: I2c>(dev: &mut I2CWrapper<I2C>) -> Result<u8, I2CError<I2C::Error>> {
+    read_register(dev, registers::STAT_REG)
 }
 
-pub fn write_standby_time<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>, standby: u8) {
-    let mut buffer = [0u8];
-    dev.read_from_register(registers::CONFIG_REG, &mut buffer).unwrap();
-    let old_state = *buffer.first().unwrap() & 0x1F;
-    let new_state = old_state | (standby << 5);
-    dev.write_to_register(registers::CONFIG_REG, &[new_state]).unwrap();
+pub fn write_field<I2C: I2c>(dev: &mut I2CWrapper<I2C>, field: FieldWrite, value: u8) -> Result<(), I2CError<I2C::Error>> {
+    update_register(dev, field.register, |state| (field.merge)(state, value))
 }
 
-pub fn write_filter<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>, filter: u8) {
-    let mut buffer = [0u8];
-    dev.read_from_register(registers::CONFIG_REG, &mut buffer).unwrap();
-    let old_state = *buffer.first().unwrap() & 0xE3;
-    let new_state = old_state | (filter << 2);
-    dev.write_to_register(registers::CONFIG_REG, &[new_state]).unwrap();
+pub fn write_config<I2C: I2c>(dev: &mut I2CWrapper<I2C>, standby: u8, filter: u8) -> Result<(), I2CError<I2C::Error>> {
+    update_register(dev, registers::CONFIG_REG, |state| fields::with_config(state, standby, filter))
 }
 
-pub fn get_temperature_raw<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u32 {
-    let mut buffer = [0u8; 3];
-    dev.read_from_register(registers::TEMPERATURE_MSB_REG, &mut buffer[0..1]).unwrap();
-    dev.read_from_register(registers::TEMPERATURE_LSB_REG, &mut buffer[1..2]).unwrap();
-    dev.read_from_register(registers::TEMPERATURE_XLSB_REG, &mut buffer[2..3]).unwrap();
+// The BMP280 has no humidity block
+pub fn read_calibration<I2C: I2c>(dev: &mut I2CWrapper<I2C>, humidity: bool) -> Result<Calibration, I2CError<I2C::Error>> {
+    let mut tp_block = [0u8; calibration::TP_BLOCK_LEN];
+    let mut h_block = [0u8; calibration::H_BLOCK_LEN];
+    dev.read_from_register(registers::DIG_T1_LSB_REG, &mut tp_block)?;
+    if humidity {
+        dev.read_from_register(registers::DIG_H2_LSB_REG, &mut h_block)?;
+    }
 
-    (u32::from(buffer[0]) << 12) | (u32::from(buffer[1]) << 4) | ((u32::from(buffer[2]) >> 4) & 0x0F)
+    Ok(Calibration::from_blocks(&tp_block, humidity.then_some(&h_block)))
 }
 
-pub fn get_pressure_raw<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u32 {
-    let mut buffer = [0u8; 3];
-    dev.read_from_register(registers::PRESSURE_MSB_REG, &mut buffer[0..1]).unwrap();
-    dev.read_from_register(registers::PRESSURE_LSB_REG, &mut buffer[1..2]).unwrap();
-    dev.read_from_register(registers::PRESSURE_XLSB_REG, &mut buffer[2..3]).unwrap();
+// Without humidity registers the humidity reading is left as skipped
+pub fn read_raw_data<I2C: I2c>(dev: &mut I2CWrapper<I2C>, humidity: bool) -> Result<fields::RawData, I2CError<I2C::Error>> {
+    let mut block = fields::SKIPPED_BLOCK;
+    let len = if humidity { fields::DATA_BLOCK_LEN } else { fields::PT_BLOCK_LEN };
+    dev.read_from_register(registers::PRESSURE_MSB_REG, &mut block[..len])?;
 
-    (u32::from(buffer[0]) << 12) | (u32::from(buffer[1]) << 4) | ((u32::from(buffer[2]) >> 4) & 0x0F)
+    Ok(fields::raw_data(&block))
 }
 
-pub fn get_humidity_raw<I2C: Write + WriteRead>(dev: &mut I2CWrapper<I2C>) -> u32 {
-    let mut buffer = [0u8; 2];
-    dev.read_from_register(registers::HUMIDITY_MSB_REG, &mut buffer[0..1]).unwrap();
-    dev.read_from_register(registers::HUMIDITY_LSB_REG, &mut buffer[1..2]).unwrap();
+fn read_register<I2C: I2c>(dev: &mut I2CWrapper<I2C>, register: u8) -> Result<u8, I2CError<I2C::Error>> {
+    let mut buffer = [0u8];
+    dev.read_from_register(register, &mut buffer)?;
+    Ok(buffer[0])
+}
 
-    (u32::from(buffer[0]) << 8) | (u32::from(buffer[1]))
+fn update_register<I2C: I2c>(dev: &mut I2CWrapper<I2C>, register: u8, update: impl FnOnce(u8) -> u8) -> Result<(), I2CError<I2C::Error>> {
+    let old_state = read_register(dev, register)?;
+    dev.write_to_register(register, &[update(old_state)])
 }
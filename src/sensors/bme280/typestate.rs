@@ -0,0 +1,177 @@
+// Mode tracked in the type, on top of the dynamic driver: configuration only
+// compiles in sleep mode, continuous reads only in normal mode, and forced
+// conversions only after choosing forced mode. Transitions consume the driver.
+
+use core::marker::PhantomData;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+use super::{calibration, BME280Config, BME280Error, BME280Measurement, Compensation, Mode, Variant};
+
+// Asleep, the only mode the datasheet allows configuration writes in
+pub struct Sleeping;
+// Asleep between one-shot conversions started by `measure`
+pub struct Forced;
+// Converting continuously every standby period
+pub struct Normal;
+
+pub struct BME280<I2C, MODE> {
+    inner: super::BME280<I2C>,
+    _mode: PhantomData<MODE>
+}
+
+impl<I2C: I2c> BME280<I2C, Sleeping> {
+    // Read the chip ID and calibration, then put the chip to sleep
+    pub fn new(dev: I2C, address: u8) -> Result<Self, BME280Error<I2C::Error>> {
+        Self::from_dynamic(super::BME280::new(dev, address)?)
+    }
+
+    // Take over a dynamic driver, whatever mode the chip was left in
+    pub fn from_dynamic(mut inner: super::BME280<I2C>) -> Result<Self, BME280Error<I2C::Error>> {
+        inner.set_mode(Mode::Sleep)?;
+        Ok(BME280::wrap(inner))
+    }
+
+    // Write oversampling, filter and standby time. The mode in `config` is
+    // ignored, the chip stays asleep until a transition.
+    pub fn configure(&mut self, config: BME280Config) -> Result<(), BME280Error<I2C::Error>> {
+        self.inner.configure(BME280Config { mode: Mode::Sleep, ..config })
+    }
+
+    pub fn into_forced(mut self) -> BME280<I2C, Forced> {
+        self.inner.update_config(|config| config.mode = Mode::Forced);
+        BME280::wrap(self.inner)
+    }
+
+    pub fn into_normal(mut self) -> Result<BME280<I2C, Normal>, BME280Error<I2C::Error>> {
+        self.inner.set_mode(Mode::Normal)?;
+        Ok(BME280::wrap(self.inner))
+    }
+}
+
+impl<I2C: I2c> BME280<I2C, Forced> {
+    // One conversion, after which the chip is back asleep
+    pub fn measure<D: DelayNs>(&mut self, delay: &mut D) -> Result<BME280Measurement, BME280Error<I2C::Error>> {
+        self.inner.measure_forced(delay)
+    }
+
+    // Nothing to write, the chip already sleeps between conversions
+    pub fn into_sleeping(mut self) -> BME280<I2C, Sleeping> {
+        self.inner.update_config(|config| config.mode = Mode::Sleep);
+        BME280::wrap(self.inner)
+    }
+}
+
+impl<I2C: I2c> BME280<I2C, Normal> {
+    // Latest completed conversion
+    pub fn measure(&mut self) -> Result<BME280Measurement, BME280Error<I2C::Error>> {
+        self.inner.measure()
+    }
+
+    pub fn into_sleeping(mut self) -> Result<BME280<I2C, Sleeping>, BME280Error<I2C::Error>> {
+        self.inner.set_mode(Mode::Sleep)?;
+        Ok(BME280::wrap(self.inner))
+    }
+}
+
+// Available in every mode
+impl<I2C: I2c, MODE> BME280<I2C, MODE> {
+    fn wrap(inner: super::BME280<I2C>) -> BME280<I2C, MODE> {
+        BME280 { inner, _mode: PhantomData }
+    }
+
+    // Back to the dynamic API, with the chip left in its current mode
+    pub fn into_dynamic(self) -> super::BME280<I2C> {
+        self.inner
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.inner.variant()
+    }
+
+    pub fn config(&self) -> Option<BME280Config> {
+        self.inner.config()
+    }
+
+    pub fn calibration(&self) -> &calibration::Calibration {
+        self.inner.calibration()
+    }
+
+    pub fn set_compensation(&mut self, compensation: Compensation) {
+        self.inner.set_compensation(compensation);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use super::{BME280, Sleeping};
+    use crate::sensors::bme280::{constants::registers, tests::get_mock_calibration, Address, BME280Config, Mode};
+
+    const DATA_BLOCK: [u8; 8] = [82, 79, 0, 128, 189, 0, 117, 97];
+
+    #[test]
+    fn configure_asleep_then_run_normal() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.extend([
+            // Put to sleep on creation
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x27]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x24]),
+            // Configured without leaving sleep
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x54]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_HUMIDITY_REG], vec![0x00]),
+            I2cTransaction::write(address, vec![registers::CTRL_HUMIDITY_REG, 0x01]),
+            I2cTransaction::write_read(address, vec![registers::CONFIG_REG], vec![0x00]),
+            I2cTransaction::write(address, vec![registers::CONFIG_REG, 0x10]),
+            // Normal mode, one read, back to sleep
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x54]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x57]),
+            I2cTransaction::write_read(address, vec![registers::PRESSURE_MSB_REG], DATA_BLOCK.to_vec()),
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x57]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x54]),
+        ]);
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut sensor: BME280<_, Sleeping> = BME280::new(i2c.clone(), address).unwrap();
+        sensor.configure(BME280Config::indoor_navigation()).unwrap();
+        assert_eq!(sensor.config().map(|config| config.mode), Some(Mode::Sleep));
+
+        let mut sensor = sensor.into_normal().unwrap();
+        assert_eq!(sensor.measure().unwrap().temperature, Some(22.81));
+        assert_eq!(sensor.config().map(|config| config.mode), Some(Mode::Normal));
+
+        let sensor = sensor.into_sleeping().unwrap();
+        assert_eq!(sensor.into_dynamic().config().map(|config| config.mode), Some(Mode::Sleep));
+        i2c.done();
+    }
+
+    #[test]
+    fn forced_conversions() {
+        let address: u8 = Address::Default.into();
+        let mut expectations = get_mock_calibration(address);
+        expectations.extend([
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x24]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x24]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_HUMIDITY_REG], vec![0x01]),
+            I2cTransaction::write_read(address, vec![registers::CTRL_MEAS_REG], vec![0x24]),
+            I2cTransaction::write(address, vec![registers::CTRL_MEAS_REG, 0x25]),
+            I2cTransaction::write_read(address, vec![registers::STAT_REG], vec![0x00]),
+            I2cTransaction::write_read(address, vec![registers::PRESSURE_MSB_REG], DATA_BLOCK.to_vec()),
+        ]);
+        let mut i2c = I2cMock::new(&expectations);
+        let mut delay = CheckedDelay::new(&[DelayTransaction::delay_us(9300)]);
+
+        let mut sensor = BME280::new(i2c.clone(), address).unwrap().into_forced();
+        assert_eq!(sensor.measure(&mut delay).unwrap().temperature, Some(22.81));
+
+        // Back to configuration without touching the chip
+        let _sensor = sensor.into_sleeping();
+        i2c.done();
+        delay.done();
+    }
+}
@@ -0,0 +1,448 @@
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+pub mod calibration;
+pub mod constants;
+mod fields;
+
+use constants::{registers, values};
+
+#[cfg(feature = "std")]
+use chrono::Utc;
+
+#[cfg(feature = "std")]
+use crate::measurement::{Measurement, Quantity, SensorId};
+use crate::protocols::i2c::{I2CError, I2CWrapper};
+use crate::sensors::bme280::{fields as shared, Filter, Mode, Oversampling, Variant};
+#[cfg(feature = "std")]
+use crate::sensors::{Sensor, SensorError};
+
+pub use constants::addresses;
+
+#[derive(Debug)]
+pub enum BME680Error<E> {
+    IOError(E),  // Bus failure, with the HAL's error
+    WriteTooLong(usize),  // Register write longer than the bus buffer
+    ChipIdMismatch { expected: u8, found: u8 },
+    InvalidHeaterProfile(u8),  // Out of range, or enabled before it was set
+    NotReady  // Still converting
+}
+
+impl<E> From<I2CError<E>> for BME680Error<E> {
+    fn from(error: I2CError<E>) -> Self {
+        match error {
+            I2CError::IOError(e) => BME680Error::IOError(e),
+            I2CError::WriteTooLong(len) => BME680Error::WriteTooLong(len)
+        }
+    }
+}
+
+// Temperature, pressure and humidity setup. The BME680 has no normal mode,
+// every conversion is forced by `measure_forced`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BME680Config {
+    pub osrs_t: Oversampling,
+    pub osrs_p: Oversampling,
+    pub osrs_h: Oversampling,
+    pub filter: Filter
+}
+
+impl Default for BME680Config {
+    // Settings of Bosch's forced mode example
+    fn default() -> Self {
+        BME680Config {
+            osrs_t: Oversampling::Ox8,
+            osrs_p: Oversampling::Ox4,
+            osrs_h: Oversampling::Ox2,
+            filter: Filter::C4
+        }
+    }
+}
+
+// Target temperature of the gas sensor hot plate and how long to hold it
+// before the gas conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaterProfile {
+    pub temperature: u16,  // °C, up to 400
+    pub duration_ms: u16  // Up to 4032
+}
+
+impl Default for HeaterProfile {
+    fn default() -> Self {
+        HeaterProfile { temperature: 320, duration_ms: 150 }
+    }
+}
+
+// Compensated readings from a single conversion, None for skipped channels
+// and for gas when it is disabled or the heater did not stabilise.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BME680Measurement {
+    pub temperature: Option<f64>,  // °C
+    pub pressure: Option<f64>,  // Pa
+    pub humidity: Option<f64>,  // %RH
+    pub gas_resistance: Option<f64>  // Ω
+}
+
+// Maximum time in µs for a forced temperature, pressure and humidity
+// conversion, as computed by Bosch's API. The heater duration comes on top.
+pub fn max_measurement_time_us(osrs_t: Oversampling, osrs_p: Oversampling, osrs_h: Oversampling) -> u32 {
+    let cycles = osrs_t.factor() + osrs_p.factor() + osrs_h.factor();
+    cycles * 1963 + 477 * 9 + 1000
+}
+
+// New data polling, after the expected conversion time
+const STATUS_POLL_INTERVAL_US: u32 = 1000;
+const STATUS_POLL_LIMIT: u32 = 20;
+// Power-on and soft reset start-up time
+const STARTUP_TIME_US: u32 = 2000;
+// Heater resistance is computed against this until a temperature is measured
+const DEFAULT_AMBIENT_TEMPERATURE: i8 = 25;
+
+pub struct BME680<I2C> {
+    dev: I2CWrapper<I2C>,
+    calibration: calibration::Calibration,
+    config: BME680Config,
+    profiles: [Option<HeaterProfile>; values::HEATER_PROFILES as usize],
+    gas_profile: Option<u8>,  // Profile used by the next conversion, None with gas off
+    ambient: i8  // °C, last measured temperature
+}
+
+impl<I2C: I2c> BME680<I2C> {
+
+    // Create new BME680 device wrapper for I2C communication
+    pub fn new(dev: I2C, address: u8) -> Result<BME680<I2C>, BME680Error<I2C::Error>> {
+        let mut wrapper = I2CWrapper::new(dev, address);
+        check_id(read_register(&mut wrapper, registers::CHIP_ID_REG)?)?;
+        let calibration = calibration::Calibration::build(&mut wrapper)?;
+        Ok(BME680 {
+            dev: wrapper,
+            calibration,
+            config: BME680Config::default(),
+            profiles: [None; values::HEATER_PROFILES as usize],
+            gas_profile: None,
+            ambient: DEFAULT_AMBIENT_TEMPERATURE
+        })
+    }
+
+    pub fn build(dev: I2C, address: u8) -> Result<BME680<I2C>, BME680Error<I2C::Error>> {
+        let mut sensor = BME680::new(dev, address)?;
+        sensor.configure(BME680Config::default())?;
+        Ok(sensor)
+    }
+
+    pub fn variant(&self) -> Variant {
+        Variant::BME680
+    }
+
+    // Write ctrl_hum and config, then ctrl_meas (ctrl_hum only takes effect
+    // after it). The chip is left asleep until `measure_forced`.
+    pub fn configure(&mut self, config: BME680Config) -> Result<(), BME680Error<I2C::Error>> {
+        let osrs_t = u8::from(config.osrs_t);
+        let osrs_p = u8::from(config.osrs_p);
+
+        update_register(&mut self.dev, registers::CTRL_HUMIDITY_REG, |state| shared::with_humidity_oversample(state, config.osrs_h.into()))?;
+        update_register(&mut self.dev, registers::CONFIG_REG, |state| shared::with_filter(state, config.filter.into()))?;
+        self.dev.write_to_register(registers::CTRL_MEAS_REG, &[shared::ctrl_meas(osrs_t, osrs_p, Mode::Sleep.into())])?;
+
+        self.config = config;
+        Ok(())
+    }
+
+    pub fn config(&self) -> BME680Config {
+        self.config
+    }
+
+    pub fn calibration(&self) -> &calibration::Calibration {
+        &self.calibration
+    }
+
+    // Program one of the ten heater set-points. The resistance is computed
+    // for the last measured temperature, re-set the profile if it drifts.
+    pub fn set_heater_profile(&mut self, index: u8, profile: HeaterProfile) -> Result<(), BME680Error<I2C::Error>> {
+        if index >= values::HEATER_PROFILES {
+            return Err(BME680Error::InvalidHeaterProfile(index))
+        }
+        let resistance = self.calibration.gas.heater_resistance(profile.temperature, self.ambient);
+        self.dev.write_to_register(registers::RES_HEAT_0_REG + index, &[resistance])?;
+        self.dev.write_to_register(registers::GAS_WAIT_0_REG + index, &[calibration::gas_wait(profile.duration_ms)])?;
+
+        self.profiles[usize::from(index)] = Some(profile);
+        Ok(())
+    }
+
+    pub fn heater_profile(&self, index: u8) -> Option<HeaterProfile> {
+        self.profiles.get(usize::from(index)).copied().flatten()
+    }
+
+    // Run a gas conversion with the given profile after each forced
+    // temperature, pressure and humidity conversion.
+    pub fn enable_gas(&mut self, index: u8) -> Result<(), BME680Error<I2C::Error>> {
+        if self.heater_profile(index).is_none() {
+            return Err(BME680Error::InvalidHeaterProfile(index))
+        }
+        update_register(&mut self.dev, registers::CTRL_GAS_0_REG, |state| fields::with_heat_off(state, false))?;
+        self.dev.write_to_register(registers::CTRL_GAS_1_REG, &[fields::ctrl_gas_1(true, index)])?;
+
+        self.gas_profile = Some(index);
+        Ok(())
+    }
+
+    pub fn disable_gas(&mut self) -> Result<(), BME680Error<I2C::Error>> {
+        self.dev.write_to_register(registers::CTRL_GAS_1_REG, &[fields::ctrl_gas_1(false, 0)])?;
+        update_register(&mut self.dev, registers::CTRL_GAS_0_REG, |state| fields::with_heat_off(state, true))?;
+
+        self.gas_profile = None;
+        Ok(())
+    }
+
+    // Get the ID of the chip
+    pub fn get_id(&mut self) -> Result<u8, BME680Error<I2C::Error>> {
+        check_id(read_register(&mut self.dev, registers::CHIP_ID_REG)?)
+    }
+
+    // Soft reset the device, then reload the calibration and re-apply the
+    // configuration and heater profiles.
+    pub fn reset<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), BME680Error<I2C::Error>> {
+        self.dev.write_to_register(registers::RST_REG, &[values::SOFT_RESET])?;
+        delay.delay_us(STARTUP_TIME_US);
+
+        self.get_id()?;
+        self.calibration = calibration::Calibration::build(&mut self.dev)?;
+        self.configure(self.config)?;
+        for (index, profile) in (0..values::HEATER_PROFILES).zip(self.profiles) {
+            if let Some(profile) = profile {
+                self.set_heater_profile(index, profile)?;
+            }
+        }
+        match self.gas_profile {
+            Some(index) => self.enable_gas(index),
+            None => Ok(())
+        }
+    }
+
+    // One-shot conversion: temperature, pressure and humidity, then gas with
+    // the enabled heater profile. Waits the expected time, then polls for new
+    // data. The chip goes back to sleep on its own.
+    pub fn measure_forced<D: DelayNs>(&mut self, delay: &mut D) -> Result<BME680Measurement, BME680Error<I2C::Error>> {
+        update_register(&mut self.dev, registers::CTRL_MEAS_REG, |state| shared::with_mode(state, Mode::Forced.into()))?;
+
+        delay.delay_us(self.forced_wait_us());
+        let field = self.wait_for_data(delay)?;
+
+        let measurement = self.calibration.compensate(&field);
+        if let Some(temperature) = measurement.temperature {
+            self.ambient = temperature.clamp(i8::MIN.into(), i8::MAX.into()) as i8;
+        }
+        Ok(measurement)
+    }
+
+    fn forced_wait_us(&self) -> u32 {
+        let heater_ms = self.gas_profile
+            .and_then(|index| self.heater_profile(index))
+            .map_or(0, |profile| u32::from(profile.duration_ms));
+        max_measurement_time_us(self.config.osrs_t, self.config.osrs_p, self.config.osrs_h) + heater_ms * 1000
+    }
+
+    fn wait_for_data<D: DelayNs>(&mut self, delay: &mut D) -> Result<fields::FieldData, BME680Error<I2C::Error>> {
+        for _ in 0..=STATUS_POLL_LIMIT {
+            let mut block = [0u8; fields::FIELD_LEN];
+            self.dev.read_from_register(registers::MEAS_STATUS_0_REG, &mut block)?;
+            let field = fields::field_data(&block);
+            if fields::new_data_bit(field.status) == 1 {
+                return Ok(field)
+            }
+            delay.delay_us(STATUS_POLL_INTERVAL_US);
+        }
+        Err(BME680Error::NotReady)
+    }
+
+}
+
+fn check_id<E>(id: u8) -> Result<u8, BME680Error<E>> {
+    match Variant::try_from(id) {
+        Ok(Variant::BME680) => Ok(id),
+        _ => Err(BME680Error::ChipIdMismatch { expected: Variant::BME680.into(), found: id })
+    }
+}
+
+fn read_register<I2C: I2c>(dev: &mut I2CWrapper<I2C>, register: u8) -> Result<u8, I2CError<I2C::Error>> {
+    let mut buffer = [0u8; 1];
+    dev.read_from_register(register, &mut buffer)?;
+    Ok(buffer[0])
+}
+
+fn update_register<I2C: I2c>(dev: &mut I2CWrapper<I2C>, register: u8, update: impl FnOnce(u8) -> u8) -> Result<(), I2CError<I2C::Error>> {
+    let state = read_register(dev, register)?;
+    dev.write_to_register(register, &[update(state)])
+}
+
+#[cfg(feature = "std")]
+impl<I2C: I2c> Sensor for BME680<I2C> {
+    type BusError = I2C::Error;
+
+    fn id(&self) -> SensorId {
+        SensorId::new(Variant::BME680.name(), self.dev.address())
+    }
+
+    // Sampling needs a forced conversion, so it waits on a std delay
+    fn sample(&mut self) -> Result<Vec<Measurement>, SensorError<I2C::Error>> {
+        let measurement = self.measure_forced(&mut StdDelay)?;
+        let timestamp = Utc::now();
+
+        // Skipped channels and gas without a stable heater are left out
+        Ok([
+            (Quantity::Temperature, measurement.temperature),
+            (Quantity::Pressure, measurement.pressure),
+            (Quantity::RelativeHumidity, measurement.humidity),
+            (Quantity::GasResistance, measurement.gas_resistance),
+        ].into_iter()
+            .filter_map(|(quantity, value)| Some(Measurement::new(self.id(), quantity, value?, timestamp)))
+            .collect())
+    }
+}
+
+#[cfg(feature = "std")]
+struct StdDelay;
+
+#[cfg(feature = "std")]
+impl DelayNs for StdDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        std::thread::sleep(std::time::Duration::from_nanos(ns.into()));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use crate::sensors::bme280::Oversampling;
+    use super::{addresses, constants::registers, BME680, BME680Config, BME680Error, HeaterProfile};
+
+    const ADDRESS: u8 = addresses::DEFAULT;
+
+    // Blocks decoding to the coefficients of `calibration::tests`
+    fn mock_calibration() -> Vec<I2cTransaction> {
+        let mut coeff_1 = vec![0u8; 25];
+        coeff_1[1..24].copy_from_slice(&[
+            0x4B, 0x66, 0x03, 0x00, 0x68, 0x8C, 0x8D, 0xD7, 0x58, 0x00, 0xCD, 0x1C, 0x70, 0xFF,
+            0x20, 0x1E, 0x00, 0x00, 0x94, 0xF4, 0x58, 0xF6, 0x1E
+        ]);
+        let coeff_2 = vec![0x3F, 0x5D, 0x2A, 0x00, 0x2D, 0x14, 0x78, 0x9C, 0x33, 0x66, 0x20, 0xD1, 0xE2, 0x12, 0x00, 0x00];
+        vec![
+            I2cTransaction::write_read(ADDRESS, vec![registers::CHIP_ID_REG], vec![0x61]),
+            I2cTransaction::write_read(ADDRESS, vec![registers::COEFF_1_REG], coeff_1),
+            I2cTransaction::write_read(ADDRESS, vec![registers::COEFF_2_REG], coeff_2),
+            I2cTransaction::write_read(ADDRESS, vec![registers::RES_HEAT_VAL_REG], vec![0x28, 0x00, 0x16, 0x00, 0x00]),
+        ]
+    }
+
+    fn field(gas_status: u8) -> I2cTransaction {
+        I2cTransaction::write_read(ADDRESS, vec![registers::MEAS_STATUS_0_REG],
+            vec![0x80, 0x00, 0x58, 0xA5, 0x00, 0x78, 0xE0, 0x00, 0x5B, 0xE4, 0, 0, 0, 0x4B, gas_status])
+    }
+
+    #[test]
+    fn rejects_other_chips() {
+        let mut i2c = I2cMock::new(&[I2cTransaction::write_read(ADDRESS, vec![registers::CHIP_ID_REG], vec![0x60])]);
+
+        let result = BME680::new(i2c.clone(), ADDRESS);
+        assert!(matches!(result, Err(BME680Error::ChipIdMismatch { expected: 0x61, found: 0x60 })));
+        i2c.done();
+    }
+
+    #[test]
+    fn configure_writes_shared_layout() {
+        let mut expectations = mock_calibration();
+        expectations.extend([
+            I2cTransaction::write_read(ADDRESS, vec![registers::CTRL_HUMIDITY_REG], vec![0x40]),
+            I2cTransaction::write(ADDRESS, vec![registers::CTRL_HUMIDITY_REG, 0x42]),
+            I2cTransaction::write_read(ADDRESS, vec![registers::CONFIG_REG], vec![0x00]),
+            I2cTransaction::write(ADDRESS, vec![registers::CONFIG_REG, 0x08]),
+            I2cTransaction::write(ADDRESS, vec![registers::CTRL_MEAS_REG, 0x8C]),
+        ]);
+        let mut i2c = I2cMock::new(&expectations);
+
+        let sensor = BME680::build(i2c.clone(), ADDRESS).unwrap();
+        assert_eq!(sensor.calibration().temperature.t1, 26163);
+        assert_eq!(sensor.calibration().gas.res_heat_range, 1);
+        i2c.done();
+    }
+
+    #[test]
+    fn heater_profiles() {
+        let mut expectations = mock_calibration();
+        expectations.extend([
+            I2cTransaction::write(ADDRESS, vec![registers::RES_HEAT_0_REG + 2, 116]),
+            I2cTransaction::write(ADDRESS, vec![registers::GAS_WAIT_0_REG + 2, 0x65]),
+            I2cTransaction::write_read(ADDRESS, vec![registers::CTRL_GAS_0_REG], vec![0x08]),
+            I2cTransaction::write(ADDRESS, vec![registers::CTRL_GAS_0_REG, 0x00]),
+            I2cTransaction::write(ADDRESS, vec![registers::CTRL_GAS_1_REG, 0x12]),
+        ]);
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut sensor = BME680::new(i2c.clone(), ADDRESS).unwrap();
+        assert!(matches!(sensor.set_heater_profile(10, HeaterProfile::default()), Err(BME680Error::InvalidHeaterProfile(10))));
+        assert!(matches!(sensor.enable_gas(2), Err(BME680Error::InvalidHeaterProfile(2))));
+        sensor.set_heater_profile(2, HeaterProfile::default()).unwrap();
+        sensor.enable_gas(2).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn forced_measurement_with_gas() {
+        let mut expectations = mock_calibration();
+        expectations.extend([
+            I2cTransaction::write(ADDRESS, vec![registers::RES_HEAT_0_REG, 116]),
+            I2cTransaction::write(ADDRESS, vec![registers::GAS_WAIT_0_REG, 0x65]),
+            I2cTransaction::write_read(ADDRESS, vec![registers::CTRL_GAS_0_REG], vec![0x00]),
+            I2cTransaction::write(ADDRESS, vec![registers::CTRL_GAS_0_REG, 0x00]),
+            I2cTransaction::write(ADDRESS, vec![registers::CTRL_GAS_1_REG, 0x10]),
+            I2cTransaction::write_read(ADDRESS, vec![registers::CTRL_MEAS_REG], vec![0x8C]),
+            I2cTransaction::write(ADDRESS, vec![registers::CTRL_MEAS_REG, 0x8D]),
+            field(0x35),
+        ]);
+        let mut i2c = I2cMock::new(&expectations);
+        // 14 oversampling cycles, then the 150 ms heater duration
+        let mut delay = CheckedDelay::new(&[DelayTransaction::delay_us(14 * 1963 + 477 * 9 + 1000 + 150_000)]);
+
+        let mut sensor = BME680::new(i2c.clone(), ADDRESS).unwrap();
+        sensor.set_heater_profile(0, HeaterProfile::default()).unwrap();
+        sensor.enable_gas(0).unwrap();
+        let measurement = sensor.measure_forced(&mut delay).unwrap();
+
+        assert_eq!(measurement.temperature, Some(23.88));
+        assert!((measurement.pressure.unwrap() - 98326.0).abs() <= 4.0);
+        assert!((measurement.humidity.unwrap() - 69.159).abs() < 0.05);
+        assert_eq!(measurement.gas_resistance, Some(295482.0));
+        i2c.done();
+        delay.done();
+    }
+
+    #[test]
+    fn unstable_heater_has_no_gas_reading() {
+        let mut expectations = mock_calibration();
+        expectations.extend([
+            I2cTransaction::write_read(ADDRESS, vec![registers::CTRL_MEAS_REG], vec![0x8C]),
+            I2cTransaction::write(ADDRESS, vec![registers::CTRL_MEAS_REG, 0x8D]),
+            field(0x25),
+        ]);
+        let mut i2c = I2cMock::new(&expectations);
+        let mut delay = CheckedDelay::new(&[DelayTransaction::delay_us(14 * 1963 + 477 * 9 + 1000)]);
+
+        let mut sensor = BME680::new(i2c.clone(), ADDRESS).unwrap();
+        let measurement = sensor.measure_forced(&mut delay).unwrap();
+
+        assert!(measurement.temperature.is_some());
+        assert_eq!(measurement.gas_resistance, None);
+        i2c.done();
+        delay.done();
+    }
+
+    #[test]
+    fn default_config() {
+        let config = BME680Config::default();
+        assert_eq!((config.osrs_t, config.osrs_p, config.osrs_h), (Oversampling::Ox8, Oversampling::Ox4, Oversampling::Ox2));
+    }
+}
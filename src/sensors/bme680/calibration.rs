@@ -0,0 +1,295 @@
+use byteorder::{ByteOrder, LittleEndian};
+use embedded_hal::i2c::I2c;
+
+use crate::protocols::i2c::{I2CError, I2CWrapper};
+use super::{constants::registers, fields::FieldData, BME680Measurement};
+
+// Calibration registers 0x89 to 0xA1 and 0xE1 to 0xF0
+pub const COEFF_1_LEN: usize = 25;
+pub const COEFF_2_LEN: usize = 16;
+// Heater and gas range registers 0x00 to 0x04
+pub const HEATER_LEN: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration {
+    pub temperature: TemperatureCalibration,
+    pub pressure: PressureCalibration,
+    pub humidity: HumidityCalibration,
+    pub gas: GasCalibration
+}
+
+impl Calibration {
+    pub fn build<I2C: I2c>(dev: &mut I2CWrapper<I2C>) -> Result<Calibration, I2CError<I2C::Error>> {
+        let mut coeff_1 = [0u8; COEFF_1_LEN];
+        let mut coeff_2 = [0u8; COEFF_2_LEN];
+        let mut heater = [0u8; HEATER_LEN];
+        dev.read_from_register(registers::COEFF_1_REG, &mut coeff_1)?;
+        dev.read_from_register(registers::COEFF_2_REG, &mut coeff_2)?;
+        dev.read_from_register(registers::RES_HEAT_VAL_REG, &mut heater)?;
+
+        Ok(Self::parse(&coeff_1, &coeff_2, &heater))
+    }
+
+    // Decode the calibration blocks, laid out as in Bosch's BME680 API
+    pub fn parse(coeff_1: &[u8; COEFF_1_LEN], coeff_2: &[u8; COEFF_2_LEN], heater: &[u8; HEATER_LEN]) -> Calibration {
+        Calibration {
+            temperature: TemperatureCalibration {
+                t1: LittleEndian::read_u16(&coeff_2[8..10]),
+                t2: LittleEndian::read_i16(&coeff_1[1..3]),
+                t3: coeff_1[3] as i8
+            },
+            pressure: PressureCalibration {
+                p1: LittleEndian::read_u16(&coeff_1[5..7]),
+                p2: LittleEndian::read_i16(&coeff_1[7..9]),
+                p3: coeff_1[9] as i8,
+                p4: LittleEndian::read_i16(&coeff_1[11..13]),
+                p5: LittleEndian::read_i16(&coeff_1[13..15]),
+                p6: coeff_1[16] as i8,
+                p7: coeff_1[15] as i8,
+                p8: LittleEndian::read_i16(&coeff_1[19..21]),
+                p9: LittleEndian::read_i16(&coeff_1[21..23]),
+                p10: coeff_1[23]
+            },
+            // H1 and H2 are 12 bit values sharing the nibbles of 0xE2
+            humidity: HumidityCalibration {
+                h1: (u16::from(coeff_2[2]) << 4) | u16::from(coeff_2[1] & 0x0F),
+                h2: (u16::from(coeff_2[0]) << 4) | u16::from(coeff_2[1] >> 4),
+                h3: coeff_2[3] as i8,
+                h4: coeff_2[4] as i8,
+                h5: coeff_2[5] as i8,
+                h6: coeff_2[6],
+                h7: coeff_2[7] as i8
+            },
+            gas: GasCalibration {
+                gh1: coeff_2[12] as i8,
+                gh2: LittleEndian::read_i16(&coeff_2[10..12]),
+                gh3: coeff_2[13] as i8,
+                res_heat_val: heater[0] as i8,
+                res_heat_range: (heater[2] & 0x30) >> 4,
+                range_sw_err: (heater[4] as i8) >> 4
+            }
+        }
+    }
+
+    // Integer formulas from Bosch's BME680 API. Gas resistance is only
+    // reported when the conversion ran with a stable heater.
+    pub fn compensate(&self, field: &FieldData) -> BME680Measurement {
+        let Some(adc_t) = field.raw.adc_t() else {
+            return BME680Measurement::default()
+        };
+        let t_fine = self.temperature.compensate_temperature(adc_t);
+        let gas_ready = field.gas_valid && field.heat_stable;
+
+        BME680Measurement {
+            temperature: Some(f64::from((t_fine * 5 + 128) >> 8) / 100.0),
+            pressure: field.raw.adc_p().map(|adc_p| f64::from(self.pressure.compensate_pressure(adc_p, t_fine))),
+            humidity: field.raw.adc_h().map(|adc_h| f64::from(self.humidity.compensate_humidity(adc_h, t_fine)) / 1000.0),
+            gas_resistance: gas_ready.then(|| f64::from(self.gas.gas_resistance(field.gas_adc, field.gas_range)))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemperatureCalibration {
+    pub t1: u16,
+    pub t2: i16,
+    pub t3: i8
+}
+
+impl TemperatureCalibration {
+    // Returns t_fine, temperature in °C is (t_fine * 5 + 128) >> 8 / 100
+    pub fn compensate_temperature(&self, adc_t: i32) -> i32 {
+        let var1 = (adc_t >> 3) - (i32::from(self.t1) << 1);
+        let var2 = (var1 * i32::from(self.t2)) >> 11;
+        let var3 = ((var1 >> 1) * (var1 >> 1)) >> 12;
+        let var3 = (var3 * (i32::from(self.t3) << 4)) >> 14;
+        var2 + var3
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PressureCalibration {
+    pub p1: u16,
+    pub p2: i16,
+    pub p3: i8,
+    pub p4: i16,
+    pub p5: i16,
+    pub p6: i8,
+    pub p7: i8,
+    pub p8: i16,
+    pub p9: i16,
+    pub p10: u8
+}
+
+impl PressureCalibration {
+    // Returns Pa
+    pub fn compensate_pressure(&self, adc_p: i32, t_fine: i32) -> u32 {
+        let var1 = (t_fine >> 1) - 64000;
+        let var2 = ((((var1 >> 2) * (var1 >> 2)) >> 11) * i32::from(self.p6)) >> 2;
+        let var2 = var2 + ((var1 * i32::from(self.p5)) << 1);
+        let var2 = (var2 >> 2) + (i32::from(self.p4) << 16);
+        let var1 = (((((var1 >> 2) * (var1 >> 2)) >> 13) * (i32::from(self.p3) << 5)) >> 3) + ((i32::from(self.p2) * var1) >> 1);
+        let var1 = ((32768 + (var1 >> 18)) * i32::from(self.p1)) >> 15;
+
+        if var1 == 0 {
+            return 0
+        }
+        let p = ((1_048_576 - adc_p) - (var2 >> 12)).wrapping_mul(3125);
+        let p = if p >= 0x4000_0000 {
+            (p / var1) << 1
+        } else {
+            (p << 1) / var1
+        };
+        let var1 = (i32::from(self.p9) * (((p >> 3) * (p >> 3)) >> 13)) >> 12;
+        let var2 = ((p >> 2) * i32::from(self.p8)) >> 13;
+        let var3 = ((p >> 8) * (p >> 8) * (p >> 8) * i32::from(self.p10)) >> 17;
+        (p + ((var1 + var2 + var3 + (i32::from(self.p7) << 7)) >> 4)) as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HumidityCalibration {
+    pub h1: u16,
+    pub h2: u16,
+    pub h3: i8,
+    pub h4: i8,
+    pub h5: i8,
+    pub h6: u8,
+    pub h7: i8
+}
+
+impl HumidityCalibration {
+    // Returns thousandths of %RH
+    pub fn compensate_humidity(&self, adc_h: i32, t_fine: i32) -> u32 {
+        let temp_scaled = (t_fine * 5 + 128) >> 8;
+        let var1 = (adc_h - i32::from(self.h1) * 16) - (((temp_scaled * i32::from(self.h3)) / 100) >> 1);
+        let var2 = (i32::from(self.h2) * (((temp_scaled * i32::from(self.h4)) / 100) +
+            (((temp_scaled * ((temp_scaled * i32::from(self.h5)) / 100)) >> 6) / 100) + (1 << 14))) >> 10;
+        let var3 = var1 * var2;
+        let var4 = ((i32::from(self.h6) << 7) + ((temp_scaled * i32::from(self.h7)) / 100)) >> 4;
+        let var5 = ((var3 >> 14) * (var3 >> 14)) >> 10;
+        let var6 = (var4 * var5) >> 1;
+        let humidity = (((var3 + var6) >> 10) * 1000) >> 12;
+
+        humidity.clamp(0, 100_000) as u32
+    }
+}
+
+// Gas range lookup tables from Bosch's BME680 API
+const GAS_RANGE_K1: [u32; 16] = [
+    2147483647, 2147483647, 2147483647, 2147483647, 2147483647, 2126008810, 2147483647, 2130303777,
+    2147483647, 2147483647, 2143188679, 2136746228, 2147483647, 2126008810, 2147483647, 2147483647
+];
+const GAS_RANGE_K2: [u32; 16] = [
+    4096000000, 2048000000, 1024000000, 512000000, 255744255, 127110228, 64000000, 32258064,
+    16016016, 8000000, 4000000, 2000000, 1000000, 500000, 250000, 125000
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasCalibration {
+    pub gh1: i8,
+    pub gh2: i16,
+    pub gh3: i8,
+    pub res_heat_val: i8,
+    pub res_heat_range: u8,
+    pub range_sw_err: i8
+}
+
+impl GasCalibration {
+    // Returns Ω from the 10 bit gas ADC value and its 4 bit range
+    pub fn gas_resistance(&self, adc_gas: u16, gas_range: u8) -> u32 {
+        let range = usize::from(gas_range & 0x0F);
+        let var1 = ((1340 + 5 * i64::from(self.range_sw_err)) * i64::from(GAS_RANGE_K1[range])) >> 16;
+        let var2 = ((i64::from(adc_gas) << 15) - 16_777_216) + var1;
+        let var3 = (i64::from(GAS_RANGE_K2[range]) * var1) >> 9;
+        ((var3 + (var2 >> 1)) / var2) as u32
+    }
+
+    // res_heat_x register value for a target heater temperature (°C, up to
+    // 400) at the given ambient temperature
+    pub fn heater_resistance(&self, target: u16, ambient: i8) -> u8 {
+        let target = i32::from(target.min(super::values::MAX_HEATER_TEMPERATURE));
+        let var1 = ((i32::from(ambient) * i32::from(self.gh3)) / 1000) * 256;
+        let var2 = (i32::from(self.gh1) + 784) * (((((i32::from(self.gh2) + 154009) * target * 5) / 100) + 3276800) / 10);
+        let var3 = var1 + var2 / 2;
+        let var4 = var3 / (i32::from(self.res_heat_range) + 4);
+        let var5 = 131 * i32::from(self.res_heat_val) + 65536;
+        let resistance_x100 = ((var4 / var5) - 250) * 34;
+        ((resistance_x100 + 50) / 100) as u8
+    }
+}
+
+// gas_wait_x register value: 6 bit duration with a x1/x4/x16/x64 multiplier,
+// saturating at 4032 ms.
+pub fn gas_wait(duration_ms: u16) -> u8 {
+    if duration_ms >= 0xFC0 {
+        return 0xFF
+    }
+    let (mut duration, mut factor) = (duration_ms, 0u8);
+    while duration > 0x3F {
+        duration /= 4;
+        factor += 1;
+    }
+    duration as u8 + factor * 64
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Coefficients of a typical part, reference values from the datasheet's
+    // floating point formulas (section 3.3).
+    fn create_calibration() -> Calibration {
+        Calibration {
+            temperature: TemperatureCalibration { t1: 26163, t2: 26187, t3: 3 },
+            pressure: PressureCalibration {
+                p1: 35944, p2: -10355, p3: 88, p4: 7373, p5: -144, p6: 30, p7: 32, p8: -2924, p9: -2472, p10: 30
+            },
+            humidity: HumidityCalibration { h1: 685, h2: 1013, h3: 0, h4: 45, h5: 20, h6: 120, h7: -100 },
+            gas: GasCalibration { gh1: -30, gh2: -12000, gh3: 18, res_heat_val: 40, res_heat_range: 1, range_sw_err: 0 }
+        }
+    }
+
+    #[test]
+    fn parse_layout() {
+        let mut coeff_1 = [0u8; COEFF_1_LEN];
+        let mut coeff_2 = [0u8; COEFF_2_LEN];
+        coeff_1[1..4].copy_from_slice(&[0x4B, 0x66, 0x03]);  // T2 26187, T3 3
+        coeff_1[15..17].copy_from_slice(&[0x20, 0x1E]);  // P7 32, P6 30
+        coeff_2[0..3].copy_from_slice(&[0x3F, 0x5D, 0x2A]);  // H2 1013, H1 685
+        coeff_2[8..10].copy_from_slice(&[0x33, 0x66]);  // T1 26163
+        coeff_2[10..14].copy_from_slice(&[0x20, 0xD1, 0xE2, 0x12]);  // GH2 -12000, GH1 -30, GH3 18
+        let heater = [0x28, 0x00, 0x16, 0x00, 0xF0];
+
+        let calibration = Calibration::parse(&coeff_1, &coeff_2, &heater);
+        assert_eq!(calibration.temperature, TemperatureCalibration { t1: 26163, t2: 26187, t3: 3 });
+        assert_eq!((calibration.pressure.p6, calibration.pressure.p7), (30, 32));
+        assert_eq!((calibration.humidity.h1, calibration.humidity.h2), (685, 1013));
+        assert_eq!(calibration.gas, GasCalibration { gh1: -30, gh2: -12000, gh3: 18, res_heat_val: 40, res_heat_range: 1, range_sw_err: -1 });
+    }
+
+    #[test]
+    fn compensate_against_float_formulas() {
+        let calibration = create_calibration();
+
+        let t_fine = calibration.temperature.compensate_temperature(0x78E00);
+        assert_eq!((t_fine * 5 + 128) >> 8, 2388);  // 23.883 °C
+        assert!(calibration.pressure.compensate_pressure(0x58A50, t_fine).abs_diff(98326) <= 4);
+        assert!(calibration.humidity.compensate_humidity(0x5BE4, t_fine).abs_diff(69159) <= 50);
+        assert_eq!(calibration.gas.gas_resistance(300, 5), 295482);  // 295482.31 Ω
+    }
+
+    #[test]
+    fn heater_settings() {
+        let gas = create_calibration().gas;
+
+        assert_eq!(gas.heater_resistance(320, 25), 116);
+        assert_eq!(gas.heater_resistance(200, 20), 85);
+        assert_eq!(gas.heater_resistance(500, 25), gas.heater_resistance(400, 25));
+        assert_eq!(gas_wait(25), 25);
+        assert_eq!(gas_wait(100), 0x59);  // 25 x4
+        assert_eq!(gas_wait(150), 0x65);  // 37 x4
+        assert_eq!(gas_wait(5000), 0xFF);
+    }
+}
@@ -0,0 +1,26 @@
+pub mod values {
+    pub const SOFT_RESET: u8 = 0xB6;
+    pub const HEATER_PROFILES: u8 = 10;  // res_heat_0..9 and gas_wait_0..9
+    pub const MAX_HEATER_TEMPERATURE: u16 = 400;  // °C
+}
+
+pub mod registers {
+    pub const RES_HEAT_VAL_REG: u8 = 0x00;  // Start of res_heat_val, res_heat_range and range_sw_err
+    pub const MEAS_STATUS_0_REG: u8 = 0x1D;  // Start of field 0 data
+    pub const RES_HEAT_0_REG: u8 = 0x5A;
+    pub const GAS_WAIT_0_REG: u8 = 0x64;
+    pub const CTRL_GAS_0_REG: u8 = 0x70;
+    pub const CTRL_GAS_1_REG: u8 = 0x71;
+    pub const CTRL_HUMIDITY_REG: u8 = 0x72;
+    pub const CTRL_MEAS_REG: u8 = 0x74;
+    pub const CONFIG_REG: u8 = 0x75;
+    pub const COEFF_1_REG: u8 = 0x89;  // Calibration 0x89 to 0xA1
+    pub const CHIP_ID_REG: u8 = 0xD0;
+    pub const RST_REG: u8 = 0xE0;
+    pub const COEFF_2_REG: u8 = 0xE1;  // Calibration 0xE1 to 0xF0
+}
+
+pub mod addresses {
+    pub const DEFAULT: u8 = 0x76;
+    pub const ALTERNATIVE: u8 = 0x77;
+}
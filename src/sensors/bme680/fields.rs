@@ -0,0 +1,71 @@
+// BME680 specific register encoding. ctrl_meas, ctrl_hum and the filter bits
+// of config share the BME280 layout and use `bme280::fields`.
+
+use crate::sensors::bme280::fields::{self, RawData, DATA_BLOCK_LEN};
+
+pub fn new_data_bit(status: u8) -> u8 {
+    // Check bit 7 is set to 1
+    (status & 0x80) >> 7
+}
+
+// ctrl_gas_1: run_gas and the heater profile to use
+pub fn ctrl_gas_1(run_gas: bool, profile: u8) -> u8 {
+    (u8::from(run_gas) << 4) | (profile & 0x0F)
+}
+
+// ctrl_gas_0: heat_off turns the heater current off
+pub fn with_heat_off(ctrl_gas_0: u8, heat_off: bool) -> u8 {
+    (ctrl_gas_0 & 0xF7) | (u8::from(heat_off) << 3)
+}
+
+// Field 0 registers 0x1D to 0x2B: status, gas index, the BME280 data layout,
+// then the gas resistance ADC value and its range.
+pub const FIELD_LEN: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldData {
+    pub status: u8,
+    pub raw: RawData,
+    pub gas_adc: u16,
+    pub gas_range: u8,
+    pub gas_valid: bool,  // A gas conversion ran
+    pub heat_stable: bool  // The heater reached its target temperature
+}
+
+pub fn field_data(block: &[u8; FIELD_LEN]) -> FieldData {
+    let mut data = [0u8; DATA_BLOCK_LEN];
+    data.copy_from_slice(&block[2..10]);
+
+    FieldData {
+        status: block[0],
+        raw: fields::raw_data(&data),
+        gas_adc: (u16::from(block[13]) << 2) | u16::from(block[14] >> 6),
+        gas_range: block[14] & 0x0F,
+        gas_valid: block[14] & 0x20 != 0,
+        heat_stable: block[14] & 0x10 != 0
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gas_control() {
+        assert_eq!(ctrl_gas_1(true, 3), 0x13);
+        assert_eq!(ctrl_gas_1(false, 3), 0x03);
+        assert_eq!(with_heat_off(0xFF, false), 0xF7);
+        assert_eq!(with_heat_off(0x00, true), 0x08);
+        assert_eq!(new_data_bit(0x80), 1);
+        assert_eq!(new_data_bit(0x7F), 0);
+    }
+
+    #[test]
+    fn field_layout() {
+        let field = field_data(&[0x80, 0x00, 0x58, 0xA5, 0x00, 0x78, 0xE0, 0x00, 0x5B, 0xE4, 0, 0, 0, 0x4B, 0x35]);
+        assert_eq!(field.raw, RawData { pressure: 0x58A50, temperature: 0x78E00, humidity: 0x5BE4 });
+        assert_eq!((field.gas_adc, field.gas_range), (300, 5));
+        assert!(field.gas_valid && field.heat_stable);
+    }
+}
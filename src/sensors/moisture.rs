@@ -1,28 +1,52 @@
 use byteorder::{BigEndian, ByteOrder};
-use embedded_hal::blocking::i2c::{Write, WriteRead};
+#[cfg(feature = "std")]
+use chrono::Utc;
+use embedded_hal::i2c::I2c;
 
-use crate::protocols::i2c::I2CWrapper;
+#[cfg(feature = "std")]
+use crate::measurement::{Measurement, Quantity, SensorId};
+use crate::protocols::i2c::{I2CError, I2CWrapper};
+#[cfg(feature = "std")]
+use crate::sensors::{Sensor, SensorError};
 
 mod constants;
+#[cfg(feature = "async")]
+mod asynch;
 use constants::{registers, addresses};
 
+#[cfg(feature = "async")]
+pub use asynch::MoistureAsync;
+
 #[derive(Debug)]
-pub enum MoistureError {
-    ConversionError(String),
-    IOError(String)
+pub enum MoistureError<E> {
+    IOError(E),  // Bus failure, with the HAL's error
+    WriteTooLong(usize),  // Register write longer than the bus buffer
+    InvalidRegisterValue { register: u8, value: u8 }
+}
+
+impl<E> From<I2CError<E>> for MoistureError<E> {
+    fn from(error: I2CError<E>) -> Self {
+        match error {
+            I2CError::IOError(e) => MoistureError::IOError(e),
+            I2CError::WriteTooLong(len) => MoistureError::WriteTooLong(len)
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Led {
     Off=0,
     On=1
 }
 
-impl From<u8> for Led {
-    fn from(item: u8) -> Self {
+impl TryFrom<u8> for Led {
+    type Error = u8;
+
+    fn try_from(item: u8) -> Result<Self, Self::Error> {
         match item {
-            0 => Self::Off,
-            1 => Self::On,
-            _ => panic!("Not expected")
+            0 => Ok(Self::Off),
+            1 => Ok(Self::On),
+            _ => Err(item)
         }
     }
 }
@@ -36,17 +60,20 @@ impl From<Led> for u8 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ErrorStatus {
     Off=0,
     On=1
 }
 
-impl From<u8> for ErrorStatus {
-    fn from(item: u8) -> Self {
+impl TryFrom<u8> for ErrorStatus {
+    type Error = u8;
+
+    fn try_from(item: u8) -> Result<Self, Self::Error> {
         match item {
-            0 => Self::Off,
-            1 => Self::On,
-            _ => panic!("Not expected")
+            0 => Ok(Self::Off),
+            1 => Ok(Self::On),
+            _ => Err(item)
         }
     }
 }
@@ -60,6 +87,7 @@ impl From<ErrorStatus> for u8 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Address {
     Default
 }
@@ -76,7 +104,7 @@ pub struct Moisture<I2C> {
     dev: I2CWrapper<I2C>
 }
 
-impl<I2C: Write + WriteRead> Moisture<I2C> {
+impl<I2C: I2c> Moisture<I2C> {
     
     pub fn new(dev: I2C, address: u8) -> Moisture<I2C> {
         let wrapper = I2CWrapper::new(dev, address);
@@ -84,51 +112,60 @@ impl<I2C: Write + WriteRead> Moisture<I2C> {
     }
 
     pub fn build(dev: I2C, address: u8) -> Moisture<I2C> {
-        let sensor = Moisture::new(dev, address);
-        sensor
+        Moisture::new(dev, address)
     }
 
-    pub fn get_moisture_level(&mut self) -> Result<u16, MoistureError> {
+    pub fn get_moisture_level(&mut self) -> Result<u16, MoistureError<I2C::Error>> {
         let mut buffer = [0u8; 2];
-        let result = self.dev.read_from_register(registers::COMMAND_GET_VALUE, &mut buffer);
-    
-        match result {
-            Ok(()) => Ok(BigEndian::read_u16(&buffer)),
-            Err(_) => Err(MoistureError::IOError(String::from("Error reading moisture level.")))
-        }
+        self.dev.read_from_register(registers::COMMAND_GET_VALUE, &mut buffer)?;
+
+        Ok(BigEndian::read_u16(&buffer))
     }
 
-    pub fn set_led(&mut self, led: Led) -> Result<(), MoistureError> {
-        let result = self.dev.write_to_register(led.into(), &[]);
-        
-        match result {
-            Ok(()) => Ok(()),
-            Err(_) => Err(MoistureError::IOError(String::from("Error setting the LED.")))
-        }
+    pub fn set_led(&mut self, led: Led) -> Result<(), MoistureError<I2C::Error>> {
+        self.dev.write_to_register(led.into(), &[])?;
+
+        Ok(())
     }
 
-    pub fn get_error_status(&mut self) -> Result<ErrorStatus, MoistureError> {
+    pub fn get_error_status(&mut self) -> Result<ErrorStatus, MoistureError<I2C::Error>> {
         let mut buffer = [0u8];
-        let result = self.dev.read_from_register(registers::SENSOR_STATUS, &mut buffer);
-    
-        match result {
-            Ok(()) => Ok((buffer.first().unwrap() & 0x01).into()),
-            Err(_) => Err(MoistureError::IOError(String::from("Error reading error status.")))
-        }
+        self.dev.read_from_register(registers::SENSOR_STATUS, &mut buffer)?;
 
+        ErrorStatus::try_from(buffer[0] & 0x01)
+            .map_err(|value| MoistureError::InvalidRegisterValue { register: registers::SENSOR_STATUS, value })
     }
 
-    pub fn set_address(&mut self, address: u8) -> Result<(), MoistureError> {
-        self.dev.write_to_register(registers::COMMAND_CHANGE_ADDRESS, &[address])
-            .map_err(|_| MoistureError::IOError(String::from("Error setting address")))
+    pub fn set_address(&mut self, address: u8) -> Result<(), MoistureError<I2C::Error>> {
+        self.dev.write_to_register(registers::COMMAND_CHANGE_ADDRESS, &[address])?;
+
+        Ok(())
     }
 
 }
 
+#[cfg(feature = "std")]
+impl<I2C: I2c> Sensor for Moisture<I2C> {
+    type BusError = I2C::Error;
+
+    fn id(&self) -> SensorId {
+        SensorId::new("moisture", self.dev.address())
+    }
+
+    fn sample(&mut self) -> Result<Vec<Measurement>, SensorError<I2C::Error>> {
+        let timestamp = Utc::now();
+        let level = self.get_moisture_level()?;
+
+        Ok(vec![
+            Measurement::new(self.id(), Quantity::Moisture, f64::from(level), timestamp),
+        ])
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
-    use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
 
     use super::Moisture;
 
@@ -140,12 +177,13 @@ mod tests {
         let expectations = [
             I2cTransaction::write_read(address, vec![registers::COMMAND_GET_VALUE], vec![0x00, 0x00]),
         ];
-        let i2c = I2cMock::new(&expectations);
+        let mut i2c = I2cMock::new(&expectations);
 
-        let mut moisture_sensor = Moisture::build(i2c, addresses::DEFAULT);
+        let mut moisture_sensor = Moisture::build(i2c.clone(), addresses::DEFAULT);
         let moisture = moisture_sensor.get_moisture_level().unwrap();
 
-        assert_eq!(moisture, 0)
+        assert_eq!(moisture, 0);
+        i2c.done();
     }
 
     #[test]
@@ -155,11 +193,12 @@ mod tests {
             I2cTransaction::write(address, vec![0x00]),
             I2cTransaction::write(address, vec![0x01]),
         ];
-        let i2c = I2cMock::new(&expectations);
+        let mut i2c = I2cMock::new(&expectations);
 
-        let mut moisture_sensor = Moisture::build(i2c, addresses::DEFAULT);
+        let mut moisture_sensor = Moisture::build(i2c.clone(), addresses::DEFAULT);
         moisture_sensor.set_led(Led::Off).unwrap();
         moisture_sensor.set_led(Led::On).unwrap();
+        i2c.done();
     }
 
     #[test]
@@ -167,14 +206,14 @@ mod tests {
         let address: u8 = Address::Default.into();
         let expectations = [
             I2cTransaction::write_read(address, vec![registers::SENSOR_STATUS], vec![0x01]),
-            I2cTransaction::write(address, vec![0x01]),
         ];
-        let i2c = I2cMock::new(&expectations);
+        let mut i2c = I2cMock::new(&expectations);
 
-        let mut moisture_sensor = Moisture::build(i2c, addresses::DEFAULT);
+        let mut moisture_sensor = Moisture::build(i2c.clone(), addresses::DEFAULT);
         let error_status: u8 = moisture_sensor.get_error_status().unwrap().into();
 
         assert_eq!(error_status, 1);
+        i2c.done();
     }
 
     #[test]
@@ -183,10 +222,30 @@ mod tests {
         let expectations = [
             I2cTransaction::write(address, vec![registers::COMMAND_CHANGE_ADDRESS, 0x50]),
         ];
-        let i2c = I2cMock::new(&expectations);
+        let mut i2c = I2cMock::new(&expectations);
 
-        let mut moisture_sensor = Moisture::build(i2c, addresses::DEFAULT);
+        let mut moisture_sensor = Moisture::build(i2c.clone(), addresses::DEFAULT);
         moisture_sensor.set_address(0x50).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn sample_moisture() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::COMMAND_GET_VALUE], vec![0x01, 0x2C]),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut moisture_sensor = Moisture::build(i2c.clone(), addresses::DEFAULT);
+        let measurements = moisture_sensor.sample().unwrap();
+
+        assert_eq!(measurements.len(), 1);
+        assert_eq!(measurements[0].quantity, Quantity::Moisture);
+        assert_eq!(measurements[0].value, 300.0);
+        assert_eq!(measurements[0].sensor_id, SensorId::new("moisture", addresses::DEFAULT));
+        i2c.done();
     }
 
 
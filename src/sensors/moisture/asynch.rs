@@ -0,0 +1,79 @@
+use byteorder::{BigEndian, ByteOrder};
+use embedded_hal_async::i2c::I2c;
+
+use crate::measurement::SensorId;
+use crate::protocols::i2c::AsyncI2CWrapper;
+
+use super::constants::registers;
+use super::{ErrorStatus, Led, MoistureError};
+
+// Async counterpart of `Moisture`.
+pub struct MoistureAsync<I2C> {
+    dev: AsyncI2CWrapper<I2C>
+}
+
+impl<I2C: I2c> MoistureAsync<I2C> {
+
+    pub fn new(dev: I2C, address: u8) -> MoistureAsync<I2C> {
+        MoistureAsync { dev: AsyncI2CWrapper::new(dev, address) }
+    }
+
+    pub fn id(&self) -> SensorId {
+        SensorId::new("moisture", self.dev.address())
+    }
+
+    pub async fn get_moisture_level(&mut self) -> Result<u16, MoistureError<I2C::Error>> {
+        let mut buffer = [0u8; 2];
+        self.dev.read_from_register(registers::COMMAND_GET_VALUE, &mut buffer).await?;
+
+        Ok(BigEndian::read_u16(&buffer))
+    }
+
+    pub async fn set_led(&mut self, led: Led) -> Result<(), MoistureError<I2C::Error>> {
+        self.dev.write_to_register(led.into(), &[]).await?;
+
+        Ok(())
+    }
+
+    pub async fn get_error_status(&mut self) -> Result<ErrorStatus, MoistureError<I2C::Error>> {
+        let mut buffer = [0u8];
+        self.dev.read_from_register(registers::SENSOR_STATUS, &mut buffer).await?;
+
+        ErrorStatus::try_from(buffer[0] & 0x01)
+            .map_err(|value| MoistureError::InvalidRegisterValue { register: registers::SENSOR_STATUS, value })
+    }
+
+    pub async fn set_address(&mut self, address: u8) -> Result<(), MoistureError<I2C::Error>> {
+        self.dev.write_to_register(registers::COMMAND_CHANGE_ADDRESS, &[address]).await?;
+
+        Ok(())
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use super::super::constants::addresses;
+    use super::*;
+
+    #[test]
+    fn read_moisture_value_async() {
+        let address = addresses::DEFAULT;
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::COMMAND_GET_VALUE], vec![0x01, 0x2C]),
+            I2cTransaction::write(address, vec![0x01]),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut moisture_sensor = MoistureAsync::new(i2c.clone(), address);
+        block_on(async {
+            assert_eq!(moisture_sensor.get_moisture_level().await.unwrap(), 300);
+            moisture_sensor.set_led(Led::On).await.unwrap();
+        });
+        i2c.done();
+    }
+}
@@ -1,20 +1,49 @@
 use byteorder::{ByteOrder, BigEndian};
-use embedded_hal::blocking::i2c::{Write, WriteRead};
+#[cfg(feature = "std")]
+use chrono::Utc;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
 
 mod constants;
-
-use crate::protocols::i2c::{self, I2CWrapper};
-
-use constants::registers;
+pub mod autorange;
+pub mod interrupt;
+pub mod power;
+#[cfg(feature = "async")]
+mod asynch;
+
+#[cfg(feature = "async")]
+pub use asynch::VEML6030Async;
+pub use autorange::{Range, RangedLux};
+pub use interrupt::{InterruptError, LuxThresholds, ThresholdEvent};
+pub use power::PowerProfile;
+
+#[cfg(feature = "std")]
+use crate::measurement::{Measurement, Quantity, SensorId};
+use crate::protocols::i2c::{self, I2CError, I2CWrapper};
+#[cfg(feature = "std")]
+use crate::sensors::{Sensor, SensorError};
+
+use constants::{fields::{self, Field}, registers};
 
 use self::constants::addresses;
 
 #[derive(Debug)]
-pub enum VEML6030Error {
-    ConversionError,
-    IOError
+pub enum VEML6030Error<E> {
+    IOError(E),  // Bus failure, with the HAL's error
+    WriteTooLong(usize),  // Register write longer than the bus buffer
+    InvalidRegisterValue { register: u8, value: u16 }
+}
+
+impl<E> From<I2CError<E>> for VEML6030Error<E> {
+    fn from(error: I2CError<E>) -> Self {
+        match error {
+            I2CError::IOError(e) => VEML6030Error::IOError(e),
+            I2CError::WriteTooLong(len) => VEML6030Error::WriteTooLong(len)
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Gain {
     X1 = 0b00,  // 1x gain
     X2 = 0b01,  // 2x gain
@@ -22,14 +51,16 @@ pub enum Gain {
     X1_4 = 0b11  // 1/4x gain
 }
 
-impl From<u16> for Gain {
-    fn from(item: u16) -> Self {
+impl TryFrom<u16> for Gain {
+    type Error = u16;
+
+    fn try_from(item: u16) -> Result<Self, Self::Error> {
         match item {
-            0 => Self::X1,
-            1 => Self::X2,
-            2 => Self::X1_8,
-            3 => Self::X1_4,
-            _ => panic!("Not expected")
+            0 => Ok(Self::X1),
+            1 => Ok(Self::X2),
+            2 => Ok(Self::X1_8),
+            3 => Ok(Self::X1_4),
+            _ => Err(item)
         }
     }
 }
@@ -45,6 +76,7 @@ impl From<Gain> for u16 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IntegrationTime {
     Ms25 = 0b1100,
     Ms50 = 0b1000,
@@ -54,16 +86,18 @@ pub enum IntegrationTime {
     Ms800 = 0b0011
 }
 
-impl From<u16> for IntegrationTime {
-    fn from(item: u16) -> Self {
+impl TryFrom<u16> for IntegrationTime {
+    type Error = u16;
+
+    fn try_from(item: u16) -> Result<Self, Self::Error> {
         match item {
-            0b1100 => Self::Ms25,
-            0b1000 => Self::Ms50,
-            0b0000 => Self::Ms100,
-            0b0001 => Self::Ms200,
-            0b0010 => Self::Ms400,
-            0b0011 => Self::Ms800,
-            _ => panic!("Not expected")
+            0b1100 => Ok(Self::Ms25),
+            0b1000 => Ok(Self::Ms50),
+            0b0000 => Ok(Self::Ms100),
+            0b0001 => Ok(Self::Ms200),
+            0b0010 => Ok(Self::Ms400),
+            0b0011 => Ok(Self::Ms800),
+            _ => Err(item)
         }
     }
 }
@@ -81,6 +115,20 @@ impl From<IntegrationTime> for u16 {
     }
 }
 
+impl IntegrationTime {
+    pub fn millis(&self) -> u32 {
+        match self {
+            IntegrationTime::Ms25 => 25,
+            IntegrationTime::Ms50 => 50,
+            IntegrationTime::Ms100 => 100,
+            IntegrationTime::Ms200 => 200,
+            IntegrationTime::Ms400 => 400,
+            IntegrationTime::Ms800 => 800
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PersistenceProtectNumber {
     N1 = 0b00,
     N2 = 0b01,
@@ -88,14 +136,16 @@ pub enum PersistenceProtectNumber {
     N8 = 0b11
 }
 
-impl From<u16> for PersistenceProtectNumber {
-    fn from(item: u16) -> Self {
+impl TryFrom<u16> for PersistenceProtectNumber {
+    type Error = u16;
+
+    fn try_from(item: u16) -> Result<Self, Self::Error> {
         match item {
-            0b00 => Self::N1,
-            0b01 => Self::N2,
-            0b10 => Self::N4,
-            0b11 => Self::N8,
-            _ => panic!("Not expected")
+            0b00 => Ok(Self::N1),
+            0b01 => Ok(Self::N2),
+            0b10 => Ok(Self::N4),
+            0b11 => Ok(Self::N8),
+            _ => Err(item)
         }
     }
 }
@@ -111,6 +161,7 @@ impl From<PersistenceProtectNumber> for u16 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PowerSavingMode {
     M1 = 0b00,  // Fastest, most current
     M2 = 0b01,
@@ -118,14 +169,16 @@ pub enum PowerSavingMode {
     M4 = 0b11  // Slowest, least current
 }
 
-impl From<u16> for PowerSavingMode {
-    fn from(item: u16) -> Self {
+impl TryFrom<u16> for PowerSavingMode {
+    type Error = u16;
+
+    fn try_from(item: u16) -> Result<Self, Self::Error> {
         match item {
-            0 => Self::M1,
-            1 => Self::M2,
-            2 => Self::M3,
-            3 => Self::M4,
-            _ => panic!("Not expected")
+            0 => Ok(Self::M1),
+            1 => Ok(Self::M2),
+            2 => Ok(Self::M3),
+            3 => Ok(Self::M4),
+            _ => Err(item)
         }
     }
 }
@@ -141,17 +194,20 @@ impl From<PowerSavingMode> for u16 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PowerSavingModeEnable {
     Disable = 0b0,
     Enable = 0b1
 }
 
-impl From<u16> for PowerSavingModeEnable {
-    fn from(item: u16) -> Self {
+impl TryFrom<u16> for PowerSavingModeEnable {
+    type Error = u16;
+
+    fn try_from(item: u16) -> Result<Self, Self::Error> {
         match item {
-            0 => Self::Disable,
-            1 => Self::Enable,
-            _ => panic!("Not expected")
+            0 => Ok(Self::Disable),
+            1 => Ok(Self::Enable),
+            _ => Err(item)
         }
     }
 }
@@ -166,17 +222,20 @@ impl From<PowerSavingModeEnable> for u16 {
 }
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InterruptEnable {
     Disable = 0b0,
     Enable = 0b1,
 }
 
-impl From<u16> for InterruptEnable {
-    fn from(item: u16) -> Self {
+impl TryFrom<u16> for InterruptEnable {
+    type Error = u16;
+
+    fn try_from(item: u16) -> Result<Self, Self::Error> {
         match item {
-            0 => Self::Disable,
-            1 => Self::Enable,
-            _ => panic!("Not expected")
+            0 => Ok(Self::Disable),
+            1 => Ok(Self::Enable),
+            _ => Err(item)
         }
     }
 }
@@ -190,17 +249,20 @@ impl From<InterruptEnable> for u16 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Shutdown {
     PowerOn,
     PowerOff
 }
 
-impl From<u16> for Shutdown {
-    fn from(item: u16) -> Self {
+impl TryFrom<u16> for Shutdown {
+    type Error = u16;
+
+    fn try_from(item: u16) -> Result<Self, Self::Error> {
         match item {
-            0 => Self::PowerOn,
-            1 => Self::PowerOff,
-            _ => panic!("Not expected")
+            0 => Ok(Self::PowerOn),
+            1 => Ok(Self::PowerOff),
+            _ => Err(item)
         }
     }
 }
@@ -214,17 +276,20 @@ impl From<Shutdown> for u16 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Threshold {
     NotExceeded = 0,
     Exceeded = 1,
 }
 
-impl From<u16> for Threshold {
-    fn from(item: u16) -> Self {
+impl TryFrom<u16> for Threshold {
+    type Error = u16;
+
+    fn try_from(item: u16) -> Result<Self, Self::Error> {
         match item {
-            0 => Self::NotExceeded,
-            1 => Self::Exceeded,
-            _ => panic!("Not expected")
+            0 => Ok(Self::NotExceeded),
+            1 => Ok(Self::Exceeded),
+            _ => Err(item)
         }
     }
 }
@@ -238,6 +303,7 @@ impl From<Threshold> for u16 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Address {
     Default,
     Alternative,
@@ -252,284 +318,410 @@ impl From<Address> for u8 {
     }
 }
 
+// Last known contents of the configuration registers, None until first
+// read or after a failed write. Only the driver writes them, so once loaded
+// they stay valid and reads and read-modify-writes skip the bus.
+#[derive(Debug, Clone, Copy, Default)]
+struct Shadow {
+    setting: Option<u16>,
+    power_save: Option<u16>
+}
+
+impl Shadow {
+    // Cache slot of a register, None for data and status registers
+    fn slot(&mut self, register: u8) -> Option<&mut Option<u16>> {
+        match register {
+            registers::SETTING_REG => Some(&mut self.setting),
+            registers::POWER_SAVE_REG => Some(&mut self.power_save),
+            _ => None
+        }
+    }
+
+    fn get(&mut self, register: u8) -> Option<u16> {
+        self.slot(register).and_then(|slot| *slot)
+    }
+
+    fn update(&mut self, register: u8, state: Option<u16>) {
+        if let Some(slot) = self.slot(register) {
+            *slot = state;
+        }
+    }
+}
+
 pub struct VEML6030<I2C> {
-    dev: i2c::I2CWrapper<I2C>
+    dev: i2c::I2CWrapper<I2C>,
+    shadow: Shadow,
+    auto_range: Option<usize>,  // Index in `autorange::RANGES` while auto-ranging
+    discard_next: bool  // The range just changed, the next sample is stale
 }
 
-impl<I2C: Write + WriteRead> VEML6030<I2C> {
+impl<I2C: I2c> VEML6030<I2C> {
     pub fn new(dev: I2C, address: u8) -> Self {
         let i2c_wrapper = I2CWrapper::new(dev, address);
-        VEML6030{dev: i2c_wrapper}
+        VEML6030{dev: i2c_wrapper, shadow: Shadow::default(), auto_range: None, discard_next: false}
     }
 
-    pub fn build(dev: I2C, address: u8) -> VEML6030<I2C> {
+    pub fn build(dev: I2C, address: u8) -> Result<VEML6030<I2C>, VEML6030Error<I2C::Error>> {
         let mut sensor = Self::new(dev, address);
 
-        sensor.set_shutdown(Shutdown::PowerOn).unwrap();
-        sensor.set_gain(Gain::X1_4).unwrap();
-        sensor.set_integration_time(IntegrationTime::Ms50).unwrap();
+        sensor.set_shutdown(Shutdown::PowerOn)?;
+        sensor.set_gain(Gain::X1_4)?;
+        sensor.set_integration_time(IntegrationTime::Ms50)?;
 
-        sensor
+        Ok(sensor)
     }
 
-    pub fn get_gain(&mut self) -> Result<Gain, VEML6030Error> {
-        let state = self.read_and_convert_to_u16(registers::SETTING_REG).unwrap();
-        let gain = clip_u16(state, 11, 2);
+    // Reload the cached configuration registers from the chip, e.g. after it
+    // lost power or another master wrote to it.
+    pub fn refresh(&mut self) -> Result<(), VEML6030Error<I2C::Error>> {
+        for register in [registers::SETTING_REG, registers::POWER_SAVE_REG] {
+            let state = self.read_and_convert_to_u16(register)?;
+            self.shadow.update(register, Some(state));
+        }
+        Ok(())
+    }
 
-        Ok(gain.into())
+    pub fn get_gain(&mut self) -> Result<Gain, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::GAIN)
     }
 
-    pub fn set_gain(&mut self, gain: Gain) -> Result<(), VEML6030Error> {
-        let old_state = self.read_and_convert_to_u16(registers::SETTING_REG).unwrap();
-        let new_state = insert_u16(old_state, 11, 2, gain.into());
-        self.convert_and_write_u16(registers::SETTING_REG, new_state).unwrap();
+    pub fn set_gain(&mut self, gain: Gain) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::GAIN, gain.into())
+    }
 
-        Ok(())
+    pub fn get_integration_time(&mut self) -> Result<IntegrationTime, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::INTEGRATION_TIME)
     }
 
-    pub fn get_integration_time(&mut self) -> Result<IntegrationTime, VEML6030Error> {
-        let state = self.read_and_convert_to_u16(registers::SETTING_REG).unwrap();
-        let integration_time = clip_u16(state, 6, 4);
+    pub fn set_integration_time(&mut self, integration_time: IntegrationTime) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::INTEGRATION_TIME, integration_time.into())
+    }
 
-        Ok(integration_time.into())
+    pub fn get_range(&mut self) -> Result<Range, VEML6030Error<I2C::Error>> {
+        let state = self.read_register(registers::SETTING_REG)?;
+        decode_range(state)
     }
 
-    pub fn set_integration_time(&mut self, integration_time: IntegrationTime) -> Result<(), VEML6030Error> {
-        let old_state = self.read_and_convert_to_u16(registers::SETTING_REG).unwrap();
-        let new_state = insert_u16(old_state, 6, 4, integration_time.into());
-        self.convert_and_write_u16(registers::SETTING_REG, new_state).unwrap();
+    // Gain and integration time in a single register write
+    pub fn set_range(&mut self, range: Range) -> Result<(), VEML6030Error<I2C::Error>> {
+        let state = self.read_register(registers::SETTING_REG)?;
+        self.convert_and_write_u16(registers::SETTING_REG, insert_range(state, range))
+    }
 
+    // Start auto-ranging from low sensitivity, see `autorange`
+    pub fn enable_auto_range(&mut self) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.set_range(autorange::RANGES[autorange::START])?;
+        self.auto_range = Some(autorange::START);
+        self.discard_next = true;
         Ok(())
     }
 
-    pub fn get_persist_protect_number(&mut self) -> Result<PersistenceProtectNumber, VEML6030Error> {
-        let state = self.read_and_convert_to_u16(registers::SETTING_REG).unwrap();
-        let ppn = clip_u16(state, 4, 2);
-        
-        Ok(ppn.into())
+    // Stop auto-ranging, the current range stays in place
+    pub fn disable_auto_range(&mut self) {
+        self.auto_range = None;
     }
 
-    pub fn set_persist_protect_number(&mut self, persistence_protect_number: PersistenceProtectNumber) -> Result<(), VEML6030Error> {
-        let old_state = self.read_and_convert_to_u16(registers::SETTING_REG).unwrap();
-        let new_state = insert_u16(old_state, 4, 2, persistence_protect_number.into());
-        self.convert_and_write_u16(registers::SETTING_REG, new_state).unwrap();
-
-        Ok(())
+    // Read lux, stepping the range up or down until the count is usable. The
+    // first sample after each change is read and discarded, so a change costs
+    // two integration times.
+    pub fn get_ambient_light_lux_auto_ranged<D: DelayNs>(&mut self, delay: &mut D) -> Result<RangedLux, VEML6030Error<I2C::Error>> {
+        if self.auto_range.is_none() {
+            self.enable_auto_range()?;
+        }
+        let mut index = self.auto_range.unwrap_or(autorange::START);
+        let mut steps = 0;
+
+        loop {
+            let range = autorange::RANGES[index];
+            if self.discard_next {
+                delay.delay_ms(range.integration_time.millis());
+                self.get_ambient_light_output()?;
+                delay.delay_ms(range.integration_time.millis());
+                self.discard_next = false;
+            }
+
+            let raw = self.get_ambient_light_output()?;
+            match autorange::next(index, raw) {
+                // Bounded in case the light changes while ranging
+                Some(next) if steps < autorange::RANGES.len() => {
+                    self.set_range(autorange::RANGES[next])?;
+                    self.auto_range = Some(next);
+                    self.discard_next = true;
+                    index = next;
+                    steps += 1;
+                }
+                _ => return Ok(ranged_lux(raw, range))
+            }
+        }
     }
 
-    pub fn get_interrupt_enabled(&mut self) -> Result<InterruptEnable, VEML6030Error> {
-        let state = self.read_and_convert_to_u16(registers::SETTING_REG).unwrap();
-        let interrupt = clip_u16(state, 1, 1);
+    pub fn get_persist_protect_number(&mut self) -> Result<PersistenceProtectNumber, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::PERSISTENCE_PROTECT_NUMBER)
+    }
 
-        Ok(interrupt.into())
+    pub fn set_persist_protect_number(&mut self, persistence_protect_number: PersistenceProtectNumber) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::PERSISTENCE_PROTECT_NUMBER, persistence_protect_number.into())
     }
 
-    pub fn set_interrupt_enabled(&mut self, interrupt_enable: InterruptEnable) -> Result<(), VEML6030Error> {
-        let old_state = self.read_and_convert_to_u16(registers::SETTING_REG).unwrap();
-        let new_sate = insert_u16(old_state, 1, 1, interrupt_enable.into());
-        self.convert_and_write_u16(registers::SETTING_REG, new_sate).unwrap();
-        
-        Ok(())
+    pub fn get_interrupt_enabled(&mut self) -> Result<InterruptEnable, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::INTERRUPT_ENABLE)
     }
 
-    pub fn get_shutdown(&mut self) -> Result<Shutdown, VEML6030Error> {
-        let state = self.read_and_convert_to_u16(registers::SETTING_REG).unwrap();
-        let shut_down = clip_u16(state, 0, 1);
+    pub fn set_interrupt_enabled(&mut self, interrupt_enable: InterruptEnable) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::INTERRUPT_ENABLE, interrupt_enable.into())
+    }
 
-        Ok(shut_down.into())
+    pub fn get_shutdown(&mut self) -> Result<Shutdown, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::SHUTDOWN)
     }
 
-    pub fn set_shutdown(&mut self, shutdown: Shutdown) -> Result<(), VEML6030Error> {
-        let old_state = self.read_and_convert_to_u16(registers::SETTING_REG).unwrap();
-        let new_sate = insert_u16(old_state, 1, 1, shutdown.into());
-        self.convert_and_write_u16(registers::SETTING_REG, new_sate).unwrap();
-        
-        Ok(())
+    pub fn set_shutdown(&mut self, shutdown: Shutdown) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::SHUTDOWN, shutdown.into())
     }
 
-    pub fn get_high_threshold_window(&mut self) -> Result<u16, VEML6030Error> {
-        let high_threhold_window = self.read_and_convert_to_u16(registers::H_THRESH_REG).unwrap();
+    pub fn get_high_threshold_window(&mut self) -> Result<u16, VEML6030Error<I2C::Error>> {
+        let high_threhold_window = self.read_and_convert_to_u16(registers::H_THRESH_REG)?;
 
         Ok(high_threhold_window)
     }
 
-    pub fn set_high_threshold_window(&mut self, threshold: u16) -> Result<(), VEML6030Error> {
-        self.convert_and_write_u16(registers::H_THRESH_REG, threshold).unwrap();
+    pub fn set_high_threshold_window(&mut self, threshold: u16) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.convert_and_write_u16(registers::H_THRESH_REG, threshold)?;
 
         Ok(())
     }
 
-    pub fn get_low_threshold_window(&mut self) -> Result<u16, VEML6030Error> {
-        let low_threshold_window = self.read_and_convert_to_u16(registers::L_THRESH_REG).unwrap();
+    pub fn get_low_threshold_window(&mut self) -> Result<u16, VEML6030Error<I2C::Error>> {
+        let low_threshold_window = self.read_and_convert_to_u16(registers::L_THRESH_REG)?;
 
         Ok(low_threshold_window)
     }
 
-    pub fn set_low_threshold_window(&mut self, threshold: u16) -> Result<(), VEML6030Error> {
-        self.convert_and_write_u16(registers::L_THRESH_REG, threshold).unwrap();
+    pub fn set_low_threshold_window(&mut self, threshold: u16) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.convert_and_write_u16(registers::L_THRESH_REG, threshold)?;
 
         Ok(())
     }
 
-    pub fn get_power_saving_mode(&mut self) -> Result<PowerSavingMode, VEML6030Error> {
-        let state = self.read_and_convert_to_u16(registers::POWER_SAVE_REG).unwrap();
-        let mode = clip_u16(state, 1, 2);
-        
-        Ok(mode.into())
+    pub fn get_power_saving_mode(&mut self) -> Result<PowerSavingMode, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::POWER_SAVING_MODE)
     }
 
-    pub fn set_power_saving_mode(&mut self, mode: PowerSavingMode) -> Result<(), VEML6030Error> {
-        let old_state = self.read_and_convert_to_u16(registers::POWER_SAVE_REG).unwrap();
-        let new_state = insert_u16(old_state, 1, 2, mode.into());
-        self.convert_and_write_u16(registers::POWER_SAVE_REG, new_state).unwrap();
-
-        Ok(())
+    pub fn set_power_saving_mode(&mut self, mode: PowerSavingMode) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::POWER_SAVING_MODE, mode.into())
     }
 
-    pub fn get_power_saving_mode_enabled(&mut self) -> Result<PowerSavingModeEnable, VEML6030Error> {
-        let state = self.read_and_convert_to_u16(registers::POWER_SAVE_REG).unwrap();
-        let enabled = clip_u16(state, 0, 1);
-
-        Ok(enabled.into())
+    pub fn get_power_saving_mode_enabled(&mut self) -> Result<PowerSavingModeEnable, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::POWER_SAVING_MODE_ENABLE)
     }
 
-    pub fn set_power_saving_mode_enabled(&mut self, enable: PowerSavingModeEnable) -> Result<(), VEML6030Error> {
-        let old_state = self.read_and_convert_to_u16(registers::POWER_SAVE_REG).unwrap();
-        let new_state = insert_u16(old_state, 0, 1, enable.into());
-        self.convert_and_write_u16(registers::POWER_SAVE_REG, new_state).unwrap();
-
-        Ok(())
+    pub fn set_power_saving_mode_enabled(&mut self, enable: PowerSavingModeEnable) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::POWER_SAVING_MODE_ENABLE, enable.into())
     }
 
-    pub fn get_ambient_light_output(&mut self) -> Result<u16, VEML6030Error> {
-        let alo = self.read_and_convert_to_u16(registers::AMBIENT_LIGHT_DATA_REG).unwrap();
+    pub fn get_ambient_light_output(&mut self) -> Result<u16, VEML6030Error<I2C::Error>> {
+        let alo = self.read_and_convert_to_u16(registers::AMBIENT_LIGHT_DATA_REG)?;
 
         Ok(alo)
     } 
 
-    pub fn get_white_light_output(&mut self) -> Result<u16, VEML6030Error> {
-        let wlo = self.read_and_convert_to_u16(registers::WHITE_LIGHT_DATA_REG).unwrap();
+    pub fn get_white_light_output(&mut self) -> Result<u16, VEML6030Error<I2C::Error>> {
+        let wlo = self.read_and_convert_to_u16(registers::WHITE_LIGHT_DATA_REG)?;
         
         Ok(wlo)
     }
 
-    pub fn get_low_threshold_exceeded(&mut self) -> Result<Threshold, VEML6030Error> {
-        let state = self.read_and_convert_to_u16(registers::INTERRUPT_REG).unwrap();
-        let threshold_exceeded = clip_u16(state, 15, 1);
-        
-        Ok(threshold_exceeded.into())
+    pub fn get_low_threshold_exceeded(&mut self) -> Result<Threshold, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::LOW_THRESHOLD_EXCEEDED)
     }
 
-    pub fn get_high_threshold_exceeded(&mut self) -> Result<Threshold, VEML6030Error> {
-        let state = self.read_and_convert_to_u16(registers::INTERRUPT_REG).unwrap();
-        let threshold_exceeded = clip_u16(state, 14, 1);
-        
-        Ok(threshold_exceeded.into())
+    pub fn get_high_threshold_exceeded(&mut self) -> Result<Threshold, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::HIGH_THRESHOLD_EXCEEDED)
+    }
+
+    pub fn convert_to_lux(&mut self, raw: u16) -> Result<f32, VEML6030Error<I2C::Error>> {
+        let gain = self.get_gain()?;
+        let integration_time = self.get_integration_time()?;
+
+        Ok(raw_to_lux(raw, gain, integration_time))
     }
 
-    pub fn convert_to_lux(&mut self, raw: u16) -> Result<f32, VEML6030Error> {
-        const LX_BIT: f32 = 0.0288;
+    pub fn convert_from_lux(&mut self, lux: f32) -> Result<u16, VEML6030Error<I2C::Error>> {
+        let gain = self.get_gain()?;
+        let integration_time = self.get_integration_time()?;
 
-        let gain = self.get_gain().unwrap();
-        let integration_time = self.get_integration_time().unwrap();
+        Ok(lux_to_raw(lux, gain, integration_time))
+    }
 
-        let float_raw: f32 = raw.into();
-        let mut lux = match integration_time {
-            IntegrationTime::Ms25 => float_raw * LX_BIT * 4.0,
-            IntegrationTime::Ms50 => float_raw * LX_BIT * 2.0,
-            IntegrationTime::Ms100 => float_raw * LX_BIT,
-            IntegrationTime::Ms200 => float_raw * LX_BIT / 2.0,
-            IntegrationTime::Ms400 => float_raw * LX_BIT / 4.0,
-            IntegrationTime::Ms800 => float_raw * LX_BIT / 8.0
-        };
+    pub fn compensate_lux(&mut self, lux: f32) -> Result<f32, VEML6030Error<I2C::Error>> {
+        Ok(compensate_lux(lux))
+    }
 
-        lux = match gain {
-            Gain::X1 => lux * 2.0,
-            Gain::X2 => lux,
-            Gain::X1_4 => lux * 8.0,
-            Gain::X1_8 => lux * 16.0
-        };
+    pub fn get_ambient_light_lux(&mut self) -> Result<f32, VEML6030Error<I2C::Error>> {
+        let raw_lux = self.get_ambient_light_output()?;
+        let lux = self.convert_to_lux(raw_lux)?;
+        let lux = self.compensate_lux(lux)?;
 
         Ok(lux)
     }
 
-    pub fn convert_from_lux(&mut self, lux: f32) -> Result<u16, VEML6030Error> {
-        const LX_BIT: f32 = 0.0288;
-        let gain = self.get_gain().unwrap();
-        let integration_time = self.get_integration_time().unwrap();
+    pub fn get_white_light_lux(&mut self) -> Result<f32, VEML6030Error<I2C::Error>> {
+        let raw_lux = self.get_white_light_output()?;
+        let lux = self.convert_to_lux(raw_lux)?;
+        let lux = self.compensate_lux(lux)?;
 
-        let mut lux = match gain {
-            Gain::X1 => lux / 2.0,
-            Gain::X2 => lux,
-            Gain::X1_4 => lux / 8.0,
-            Gain::X1_8 => lux / 16.0
-        };
+        Ok(lux)
+    }
 
-        lux = match integration_time {
-            IntegrationTime::Ms25 => lux / (LX_BIT * 4.0),
-            IntegrationTime::Ms50 => lux / (LX_BIT * 2.0),
-            IntegrationTime::Ms100 => lux / (LX_BIT),
-            IntegrationTime::Ms200 => lux / (LX_BIT * 2.0),
-            IntegrationTime::Ms400 => lux / (LX_BIT * 4.0),
-            IntegrationTime::Ms800 => lux / (LX_BIT * 8.0)
-        };
+    // BREAK to Common methods
 
-        Ok(lux as u16)
+    fn read_field<T: TryFrom<u16, Error = u16>>(&mut self, field: Field) -> Result<T, VEML6030Error<I2C::Error>> {
+        let state = self.read_register(field.register)?;
+        decode(field.register, clip_field(state, field))
+    }
 
+    fn write_field(&mut self, field: Field, value: u16) -> Result<(), VEML6030Error<I2C::Error>> {
+        let old_state = self.read_register(field.register)?;
+        self.convert_and_write_u16(field.register, insert_field(old_state, field, value))
     }
 
-    pub fn compensate_lux(&mut self, lux: f32) -> Result<f32, VEML6030Error> {
-        if lux > 1000. {
-            return Ok(0.00000000000060135 * lux.powi(4)
-                - 0.0000000093924 * lux.powi(3)
-                + 0.000081488 * lux.powi(2)
-                + 1.0023 * lux)
+    // Configuration registers come from the shadow once loaded
+    fn read_register(&mut self, register: u8) -> Result<u16, VEML6030Error<I2C::Error>> {
+        if let Some(state) = self.shadow.get(register) {
+            return Ok(state)
         }
-        Ok(lux)
+        let state = self.read_and_convert_to_u16(register)?;
+        self.shadow.update(register, Some(state));
+        Ok(state)
     }
 
-    pub fn get_ambient_light_lux(&mut self) -> Result<f32, VEML6030Error> {
-        let raw_lux = self.get_ambient_light_output().unwrap();
-        let lux = self.convert_to_lux(raw_lux).unwrap();
-        let lux = self.compensate_lux(lux).unwrap();
+    fn read_and_convert_to_u16(&mut self, register: u8) -> Result<u16, VEML6030Error<I2C::Error>> {
+        let mut buffer = [0u8; 2];
+        self.dev.read_from_register(register, &mut buffer)?;
+        Ok(convert_buffer_to_u16(&buffer))
+    }
 
-        Ok(lux)
+    fn convert_and_write_u16(&mut self, register: u8, state: u16) -> Result<(), VEML6030Error<I2C::Error>> {
+        let mut buffer = [0u8; 2];
+        convert_u16_to_buffer(&mut buffer, state);
+        // A failed write may or may not have landed, read it again next time
+        self.shadow.update(register, None);
+        self.dev.write_to_register(register, &buffer)?;
+        self.shadow.update(register, Some(state));
+        Ok(())
     }
 
-    pub fn get_white_light_lux(&mut self) -> Result<f32, VEML6030Error> {
-        let raw_lux = self.get_white_light_output().unwrap();
-        let lux = self.convert_to_lux(raw_lux).unwrap();
-        let lux = self.compensate_lux(lux).unwrap();
+}
+
+#[cfg(feature = "std")]
+impl<I2C: I2c> Sensor for VEML6030<I2C> {
+    type BusError = I2C::Error;
 
-        Ok(lux)
+    fn id(&self) -> SensorId {
+        SensorId::new("veml6030", self.dev.address())
     }
 
-    // BREAK to Common methods
+    fn sample(&mut self) -> Result<Vec<Measurement>, SensorError<I2C::Error>> {
+        let timestamp = Utc::now();
+        let lux = self.get_ambient_light_lux()?;
 
-    fn read_and_convert_to_u16(&mut self, register: u8) -> Result<u16, VEML6030Error> {
-        let mut buffer = [0u8; 2];
-        self.dev.read_from_register(register, &mut buffer).unwrap();
-        let state = convert_buffer_to_u16(&buffer).unwrap();
-        Ok(state)
+        Ok(vec![
+            Measurement::new(self.id(), Quantity::AmbientLight, f64::from(lux), timestamp),
+        ])
     }
+}
 
-    fn convert_and_write_u16(&mut self, register: u8, state: u16) -> Result<(), VEML6030Error> {
-        let mut buffer = [0u8; 2];
-        convert_u16_to_buffer(&mut buffer, state).unwrap();
-        self.dev.write_to_register(register, &buffer).unwrap();
-        Ok(())
-    }
+// Datasheet resolution in lux per count. Rows by integration time, 25 ms to
+// 800 ms, columns by gain: x2, x1, x1/4, x1/8. Doubling either halves it.
+const RESOLUTION: [[f32; 4]; 6] = [
+    [0.1152, 0.2304, 0.9216, 1.8432],
+    [0.0576, 0.1152, 0.4608, 0.9216],
+    [0.0288, 0.0576, 0.2304, 0.4608],
+    [0.0144, 0.0288, 0.1152, 0.2304],
+    [0.0072, 0.0144, 0.0576, 0.1152],
+    [0.0036, 0.0072, 0.0288, 0.0576],
+];
+
+// Lux per count at a gain and integration time
+pub fn resolution(gain: Gain, integration_time: IntegrationTime) -> f32 {
+    let row = match integration_time {
+        IntegrationTime::Ms25 => 0,
+        IntegrationTime::Ms50 => 1,
+        IntegrationTime::Ms100 => 2,
+        IntegrationTime::Ms200 => 3,
+        IntegrationTime::Ms400 => 4,
+        IntegrationTime::Ms800 => 5
+    };
+    let column = match gain {
+        Gain::X2 => 0,
+        Gain::X1 => 1,
+        Gain::X1_4 => 2,
+        Gain::X1_8 => 3
+    };
+    RESOLUTION[row][column]
+}
+
+// Brightest light measurable before the count saturates, before the
+// high-lux correction of `compensate_lux`
+pub fn max_lux(gain: Gain, integration_time: IntegrationTime) -> f32 {
+    raw_to_lux(u16::MAX, gain, integration_time)
+}
 
+// Conversions shared by the blocking and async drivers.
+fn raw_to_lux(raw: u16, gain: Gain, integration_time: IntegrationTime) -> f32 {
+    f32::from(raw) * resolution(gain, integration_time)
 }
 
-fn convert_buffer_to_u16(buffer: &[u8]) -> Result<u16, ()> {
-    let num = BigEndian::read_u16(buffer);
-    Ok(num)
+// Nearest count, saturating at both ends of the register
+fn lux_to_raw(lux: f32, gain: Gain, integration_time: IntegrationTime) -> u16 {
+    libm::roundf(lux / resolution(gain, integration_time)) as u16
 }
 
-fn convert_u16_to_buffer(buffer: &mut [u8], num: u16) -> Result<(), ()> {
+fn ranged_lux(raw: u16, range: Range) -> RangedLux {
+    RangedLux { lux: compensate_lux(raw_to_lux(raw, range.gain, range.integration_time)), raw, range }
+}
+
+fn decode_range<E>(setting: u16) -> Result<Range, VEML6030Error<E>> {
+    Ok(Range {
+        gain: decode(registers::SETTING_REG, clip_field(setting, fields::GAIN))?,
+        integration_time: decode(registers::SETTING_REG, clip_field(setting, fields::INTEGRATION_TIME))?
+    })
+}
+
+fn insert_range(setting: u16, range: Range) -> u16 {
+    let setting = insert_field(setting, fields::GAIN, range.gain.into());
+    insert_field(setting, fields::INTEGRATION_TIME, range.integration_time.into())
+}
+
+fn compensate_lux(lux: f32) -> f32 {
+    if lux > 1000. {
+        let lux2 = lux * lux;
+        return 0.00000000000060135 * lux2 * lux2
+            - 0.0000000093924 * lux2 * lux
+            + 0.000081488 * lux2
+            + 1.0023 * lux
+    }
+    lux
+}
+
+fn convert_buffer_to_u16(buffer: &[u8]) -> u16 {
+    BigEndian::read_u16(buffer)
+}
+
+fn convert_u16_to_buffer(buffer: &mut [u8], num: u16) {
     BigEndian::write_u16(buffer, num);
-    Ok(())
+}
+
+fn decode<T: TryFrom<u16, Error = u16>, E>(register: u8, value: u16) -> Result<T, VEML6030Error<E>> {
+    T::try_from(value).map_err(|value| VEML6030Error::InvalidRegisterValue { register, value })
+}
+
+fn clip_field(state: u16, field: Field) -> u16 {
+    clip_u16(state, field.trailing_zeros, field.length)
+}
+
+fn insert_field(state: u16, field: Field, value: u16) -> u16 {
+    insert_u16(state, field.trailing_zeros, field.length, value)
 }
 
 fn clip_u16(state: u16, trailing_zeros: u16, length: u16) -> u16 {
@@ -554,7 +746,10 @@ fn create_mask(trailing_zeros: u16, length: u16) -> u16 {
 
 #[cfg(test)]
 mod tests {
-    use embedded_hal_mock::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal::i2c::ErrorKind;
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use proptest::prelude::*;
 
     use crate::sensors::veml6030::constants::addresses;
 
@@ -564,16 +759,217 @@ mod tests {
     fn start_veml6030() {
         let address: u8 = Address::Default.into();
         let expectations = [
+            // Read once, then every field write starts from the shadow copy
             I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x00]),
             I2cTransaction::write(address, vec![registers::SETTING_REG, 0x00, 0x00]),
-            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x00]),
             I2cTransaction::write(address, vec![registers::SETTING_REG, 0x18, 0x00]),
-            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x18, 0x00]),
             I2cTransaction::write(address, vec![registers::SETTING_REG, 0x1A, 0x00]),
         ];
-        let i2c = I2cMock::new(&expectations);
+        let mut i2c = I2cMock::new(&expectations);
+
+        let _veml6030 = VEML6030::build(i2c.clone(), addresses::DEFAULT).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn invalid_integration_time() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            // Integration time bits set to the reserved 0b0100
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x01, 0x00]),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut veml6030 = VEML6030::new(i2c.clone(), addresses::DEFAULT);
+        let result = veml6030.get_integration_time();
+
+        assert!(matches!(
+            result,
+            Err(VEML6030Error::InvalidRegisterValue { register: registers::SETTING_REG, value: 0b0100 })
+        ));
+        i2c.done();
+    }
+
+    fn als(address: u8, raw: u16) -> I2cTransaction {
+        I2cTransaction::write_read(address, vec![registers::AMBIENT_LIGHT_DATA_REG], raw.to_be_bytes().to_vec())
+    }
+
+    // Delays and reads of the sample discarded after a range change
+    fn discarded(address: u8, ms: u32) -> ([DelayTransaction; 2], I2cTransaction) {
+        ([DelayTransaction::delay_ms(ms), DelayTransaction::delay_ms(ms)], als(address, 0))
+    }
+
+    #[test]
+    fn auto_range_steps_up_in_the_dark() {
+        let address: u8 = Address::Default.into();
+        let (start_delays, start_discard) = discarded(address, 100);
+        let expectations = [
+            // Gain 1/8, 100 ms
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x01]),
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x10, 0x01]),
+            start_discard.clone(),
+            als(address, 50),
+            // Gain 1/4 in one write, shutdown bit untouched
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x18, 0x01]),
+            start_discard,
+            als(address, 500),
+            // Settled: one read, no waiting
+            als(address, 400),
+        ];
+        let delays: Vec<DelayTransaction> = start_delays.iter().chain(start_delays.iter()).cloned().collect();
+        let mut i2c = I2cMock::new(&expectations);
+        let mut delay = CheckedDelay::new(&delays);
+
+        let mut veml6030 = VEML6030::new(i2c.clone(), address);
+        let reading = veml6030.get_ambient_light_lux_auto_ranged(&mut delay).unwrap();
+        assert_eq!(reading.range, Range { gain: Gain::X1_4, integration_time: IntegrationTime::Ms100 });
+        assert_eq!(reading.raw, 500);
+        assert!((reading.lux - 115.2).abs() < 1e-3);
+
+        let reading = veml6030.get_ambient_light_lux_auto_ranged(&mut delay).unwrap();
+        assert_eq!((reading.raw, reading.range.gain), (400, Gain::X1_4));
+        i2c.done();
+        delay.done();
+    }
+
+    #[test]
+    fn auto_range_steps_down_in_sunlight() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x00]),
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x10, 0x00]),
+            als(address, 0),
+            als(address, 20000),
+            // 50 ms, then 25 ms
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x12, 0x00]),
+            als(address, 0),
+            als(address, 12000),
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x13, 0x00]),
+            als(address, 0),
+            als(address, 6000),
+        ];
+        let delays = [100, 100, 50, 50, 25, 25].map(DelayTransaction::delay_ms);
+        let mut i2c = I2cMock::new(&expectations);
+        let mut delay = CheckedDelay::new(&delays);
+
+        let mut veml6030 = VEML6030::new(i2c.clone(), address);
+        let reading = veml6030.get_ambient_light_lux_auto_ranged(&mut delay).unwrap();
+        assert_eq!(reading.range, Range { gain: Gain::X1_8, integration_time: IntegrationTime::Ms25 });
+        // 11059.2 lux before the high-lux correction
+        assert!(reading.lux > 11059.2);
+        i2c.done();
+        delay.done();
+    }
+
+    #[test]
+    fn range_read_and_written_together() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x00]),
+            // Read back from the shadow copy
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x08, 0xC0]),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+        let range = Range { gain: Gain::X2, integration_time: IntegrationTime::Ms800 };
+
+        let mut veml6030 = VEML6030::new(i2c.clone(), address);
+        veml6030.set_range(range).unwrap();
+        assert_eq!(veml6030.get_range().unwrap(), range);
+        i2c.done();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn sample_veml6030() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::AMBIENT_LIGHT_DATA_REG], vec![0x00, 0x64]),
+            // Gain and integration time from one read of the setting register
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x00]),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut veml6030 = VEML6030::new(i2c.clone(), addresses::DEFAULT);
+        let measurements = veml6030.sample().unwrap();
+
+        // Gain x1 and 100ms integration time: 0.0576 lux per count
+        assert_eq!(measurements.len(), 1);
+        assert_eq!(measurements[0].quantity, Quantity::AmbientLight);
+        assert!((measurements[0].value - 5.76).abs() < 1e-4);
+        i2c.done();
+    }
+
+    #[test]
+    fn resolution_per_datasheet() {
+        assert_eq!(resolution(Gain::X2, IntegrationTime::Ms800), 0.0036);
+        assert_eq!(resolution(Gain::X1, IntegrationTime::Ms200), 0.0288);
+        assert_eq!(resolution(Gain::X1_8, IntegrationTime::Ms25), 1.8432);
+        // 65535 counts, the datasheet rounds with 65536
+        assert!((max_lux(Gain::X2, IntegrationTime::Ms800) - 235.9).abs() < 0.1);
+        assert!((max_lux(Gain::X1_8, IntegrationTime::Ms25) - 120794.1).abs() < 0.1);
+        // Both directions agree where they used not to
+        assert_eq!(lux_to_raw(28.8, Gain::X1, IntegrationTime::Ms200), 1000);
+        assert_eq!(lux_to_raw(1e6, Gain::X1, IntegrationTime::Ms200), u16::MAX);
+        assert_eq!(lux_to_raw(-1.0, Gain::X1, IntegrationTime::Ms200), 0);
+    }
+
+    fn gains() -> impl Strategy<Value = Gain> {
+        prop_oneof![Just(Gain::X1), Just(Gain::X2), Just(Gain::X1_4), Just(Gain::X1_8)]
+    }
+
+    fn integration_times() -> impl Strategy<Value = IntegrationTime> {
+        prop_oneof![
+            Just(IntegrationTime::Ms25),
+            Just(IntegrationTime::Ms50),
+            Just(IntegrationTime::Ms100),
+            Just(IntegrationTime::Ms200),
+            Just(IntegrationTime::Ms400),
+            Just(IntegrationTime::Ms800),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn raw_round_trips(raw: u16, gain in gains(), integration_time in integration_times()) {
+            prop_assert_eq!(lux_to_raw(raw_to_lux(raw, gain, integration_time), gain, integration_time), raw);
+        }
+
+        #[test]
+        fn lux_round_trips_within_half_a_count(fraction in 0.0f32..=1.0, gain in gains(), integration_time in integration_times()) {
+            let lux = fraction * max_lux(gain, integration_time);
+            let round_trip = raw_to_lux(lux_to_raw(lux, gain, integration_time), gain, integration_time);
+            // Half a count, plus f32 rounding near half-way points
+            prop_assert!((round_trip - lux).abs() <= resolution(gain, integration_time) / 2.0 + lux * 4.0 * f32::EPSILON);
+        }
+    }
+
+    #[test]
+    fn one_transaction_per_reading_after_refresh() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x00]),
+            I2cTransaction::write_read(address, vec![registers::POWER_SAVE_REG], vec![0x00, 0x00]),
+            als(address, 100),
+            // Gain x2 kept in the shadow copy as it is written
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x08, 0x00]),
+            als(address, 100),
+            // A failed write leaves the register unknown until read again
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x18, 0x00]).with_error(ErrorKind::Other),
+            als(address, 100),
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x08, 0x00]),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut veml6030 = VEML6030::new(i2c.clone(), address);
+        veml6030.refresh().unwrap();
+        assert!((veml6030.get_ambient_light_lux().unwrap() - 5.76).abs() < 1e-4);
+
+        veml6030.set_gain(Gain::X2).unwrap();
+        assert!((veml6030.get_ambient_light_lux().unwrap() - 2.88).abs() < 1e-4);
 
-        let _veml6030 = VEML6030::build(i2c, addresses::DEFAULT);
+        assert!(veml6030.set_gain(Gain::X1_4).is_err());
+        assert!((veml6030.get_ambient_light_lux().unwrap() - 2.88).abs() < 1e-4);
+        i2c.done();
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,318 @@
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::I2c;
+
+use crate::measurement::SensorId;
+use crate::protocols::i2c::AsyncI2CWrapper;
+
+use super::constants::{fields::{self, Field}, registers};
+use super::{
+    autorange, clip_field, compensate_lux, convert_buffer_to_u16, convert_u16_to_buffer, decode, decode_range, insert_field,
+    insert_range, lux_to_raw, ranged_lux, raw_to_lux, Gain, IntegrationTime, InterruptEnable, PersistenceProtectNumber,
+    PowerSavingMode, PowerSavingModeEnable, Range, RangedLux, Shadow, Shutdown, Threshold, VEML6030Error
+};
+
+// Async counterpart of `VEML6030`, sharing its field layout and lux conversion.
+pub struct VEML6030Async<I2C> {
+    dev: AsyncI2CWrapper<I2C>,
+    shadow: Shadow,
+    auto_range: Option<usize>,
+    discard_next: bool
+}
+
+impl<I2C: I2c> VEML6030Async<I2C> {
+    pub fn new(dev: I2C, address: u8) -> Self {
+        VEML6030Async { dev: AsyncI2CWrapper::new(dev, address), shadow: Shadow::default(), auto_range: None, discard_next: false }
+    }
+
+    pub fn id(&self) -> SensorId {
+        SensorId::new("veml6030", self.dev.address())
+    }
+
+    pub async fn build(dev: I2C, address: u8) -> Result<VEML6030Async<I2C>, VEML6030Error<I2C::Error>> {
+        let mut sensor = Self::new(dev, address);
+
+        sensor.set_shutdown(Shutdown::PowerOn).await?;
+        sensor.set_gain(Gain::X1_4).await?;
+        sensor.set_integration_time(IntegrationTime::Ms50).await?;
+
+        Ok(sensor)
+    }
+
+    pub async fn refresh(&mut self) -> Result<(), VEML6030Error<I2C::Error>> {
+        for register in [registers::SETTING_REG, registers::POWER_SAVE_REG] {
+            let state = self.read_and_convert_to_u16(register).await?;
+            self.shadow.update(register, Some(state));
+        }
+        Ok(())
+    }
+
+    pub async fn get_gain(&mut self) -> Result<Gain, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::GAIN).await
+    }
+
+    pub async fn set_gain(&mut self, gain: Gain) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::GAIN, gain.into()).await
+    }
+
+    pub async fn get_integration_time(&mut self) -> Result<IntegrationTime, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::INTEGRATION_TIME).await
+    }
+
+    pub async fn set_integration_time(&mut self, integration_time: IntegrationTime) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::INTEGRATION_TIME, integration_time.into()).await
+    }
+
+    pub async fn get_range(&mut self) -> Result<Range, VEML6030Error<I2C::Error>> {
+        let state = self.read_register(registers::SETTING_REG).await?;
+        decode_range(state)
+    }
+
+    pub async fn set_range(&mut self, range: Range) -> Result<(), VEML6030Error<I2C::Error>> {
+        let state = self.read_register(registers::SETTING_REG).await?;
+        self.convert_and_write_u16(registers::SETTING_REG, insert_range(state, range)).await
+    }
+
+    pub async fn enable_auto_range(&mut self) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.set_range(autorange::RANGES[autorange::START]).await?;
+        self.auto_range = Some(autorange::START);
+        self.discard_next = true;
+        Ok(())
+    }
+
+    pub fn disable_auto_range(&mut self) {
+        self.auto_range = None;
+    }
+
+    pub async fn get_ambient_light_lux_auto_ranged<D: DelayNs>(&mut self, delay: &mut D) -> Result<RangedLux, VEML6030Error<I2C::Error>> {
+        if self.auto_range.is_none() {
+            self.enable_auto_range().await?;
+        }
+        let mut index = self.auto_range.unwrap_or(autorange::START);
+        let mut steps = 0;
+
+        loop {
+            let range = autorange::RANGES[index];
+            if self.discard_next {
+                delay.delay_ms(range.integration_time.millis()).await;
+                self.get_ambient_light_output().await?;
+                delay.delay_ms(range.integration_time.millis()).await;
+                self.discard_next = false;
+            }
+
+            let raw = self.get_ambient_light_output().await?;
+            match autorange::next(index, raw) {
+                Some(next) if steps < autorange::RANGES.len() => {
+                    self.set_range(autorange::RANGES[next]).await?;
+                    self.auto_range = Some(next);
+                    self.discard_next = true;
+                    index = next;
+                    steps += 1;
+                }
+                _ => return Ok(ranged_lux(raw, range))
+            }
+        }
+    }
+
+    pub async fn get_persist_protect_number(&mut self) -> Result<PersistenceProtectNumber, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::PERSISTENCE_PROTECT_NUMBER).await
+    }
+
+    pub async fn set_persist_protect_number(&mut self, persistence_protect_number: PersistenceProtectNumber) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::PERSISTENCE_PROTECT_NUMBER, persistence_protect_number.into()).await
+    }
+
+    pub async fn get_interrupt_enabled(&mut self) -> Result<InterruptEnable, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::INTERRUPT_ENABLE).await
+    }
+
+    pub async fn set_interrupt_enabled(&mut self, interrupt_enable: InterruptEnable) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::INTERRUPT_ENABLE, interrupt_enable.into()).await
+    }
+
+    pub async fn get_shutdown(&mut self) -> Result<Shutdown, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::SHUTDOWN).await
+    }
+
+    pub async fn set_shutdown(&mut self, shutdown: Shutdown) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::SHUTDOWN, shutdown.into()).await
+    }
+
+    pub async fn get_high_threshold_window(&mut self) -> Result<u16, VEML6030Error<I2C::Error>> {
+        self.read_and_convert_to_u16(registers::H_THRESH_REG).await
+    }
+
+    pub async fn set_high_threshold_window(&mut self, threshold: u16) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.convert_and_write_u16(registers::H_THRESH_REG, threshold).await
+    }
+
+    pub async fn get_low_threshold_window(&mut self) -> Result<u16, VEML6030Error<I2C::Error>> {
+        self.read_and_convert_to_u16(registers::L_THRESH_REG).await
+    }
+
+    pub async fn set_low_threshold_window(&mut self, threshold: u16) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.convert_and_write_u16(registers::L_THRESH_REG, threshold).await
+    }
+
+    pub async fn get_power_saving_mode(&mut self) -> Result<PowerSavingMode, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::POWER_SAVING_MODE).await
+    }
+
+    pub async fn set_power_saving_mode(&mut self, mode: PowerSavingMode) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::POWER_SAVING_MODE, mode.into()).await
+    }
+
+    pub async fn get_power_saving_mode_enabled(&mut self) -> Result<PowerSavingModeEnable, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::POWER_SAVING_MODE_ENABLE).await
+    }
+
+    pub async fn set_power_saving_mode_enabled(&mut self, enable: PowerSavingModeEnable) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.write_field(fields::POWER_SAVING_MODE_ENABLE, enable.into()).await
+    }
+
+    pub async fn get_ambient_light_output(&mut self) -> Result<u16, VEML6030Error<I2C::Error>> {
+        self.read_and_convert_to_u16(registers::AMBIENT_LIGHT_DATA_REG).await
+    }
+
+    pub async fn get_white_light_output(&mut self) -> Result<u16, VEML6030Error<I2C::Error>> {
+        self.read_and_convert_to_u16(registers::WHITE_LIGHT_DATA_REG).await
+    }
+
+    pub async fn get_low_threshold_exceeded(&mut self) -> Result<Threshold, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::LOW_THRESHOLD_EXCEEDED).await
+    }
+
+    pub async fn get_high_threshold_exceeded(&mut self) -> Result<Threshold, VEML6030Error<I2C::Error>> {
+        self.read_field(fields::HIGH_THRESHOLD_EXCEEDED).await
+    }
+
+    pub async fn convert_to_lux(&mut self, raw: u16) -> Result<f32, VEML6030Error<I2C::Error>> {
+        let gain = self.get_gain().await?;
+        let integration_time = self.get_integration_time().await?;
+
+        Ok(raw_to_lux(raw, gain, integration_time))
+    }
+
+    pub async fn convert_from_lux(&mut self, lux: f32) -> Result<u16, VEML6030Error<I2C::Error>> {
+        let gain = self.get_gain().await?;
+        let integration_time = self.get_integration_time().await?;
+
+        Ok(lux_to_raw(lux, gain, integration_time))
+    }
+
+    pub async fn get_ambient_light_lux(&mut self) -> Result<f32, VEML6030Error<I2C::Error>> {
+        let raw_lux = self.get_ambient_light_output().await?;
+        let lux = self.convert_to_lux(raw_lux).await?;
+
+        Ok(compensate_lux(lux))
+    }
+
+    pub async fn get_white_light_lux(&mut self) -> Result<f32, VEML6030Error<I2C::Error>> {
+        let raw_lux = self.get_white_light_output().await?;
+        let lux = self.convert_to_lux(raw_lux).await?;
+
+        Ok(compensate_lux(lux))
+    }
+
+    async fn read_field<T: TryFrom<u16, Error = u16>>(&mut self, field: Field) -> Result<T, VEML6030Error<I2C::Error>> {
+        let state = self.read_register(field.register).await?;
+        decode(field.register, clip_field(state, field))
+    }
+
+    async fn write_field(&mut self, field: Field, value: u16) -> Result<(), VEML6030Error<I2C::Error>> {
+        let old_state = self.read_register(field.register).await?;
+        self.convert_and_write_u16(field.register, insert_field(old_state, field, value)).await
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u16, VEML6030Error<I2C::Error>> {
+        if let Some(state) = self.shadow.get(register) {
+            return Ok(state)
+        }
+        let state = self.read_and_convert_to_u16(register).await?;
+        self.shadow.update(register, Some(state));
+        Ok(state)
+    }
+
+    async fn read_and_convert_to_u16(&mut self, register: u8) -> Result<u16, VEML6030Error<I2C::Error>> {
+        let mut buffer = [0u8; 2];
+        self.dev.read_from_register(register, &mut buffer).await?;
+        Ok(convert_buffer_to_u16(&buffer))
+    }
+
+    async fn convert_and_write_u16(&mut self, register: u8, state: u16) -> Result<(), VEML6030Error<I2C::Error>> {
+        let mut buffer = [0u8; 2];
+        convert_u16_to_buffer(&mut buffer, state);
+        self.shadow.update(register, None);
+        self.dev.write_to_register(register, &buffer).await?;
+        self.shadow.update(register, Some(state));
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use super::super::constants::addresses;
+    use super::*;
+
+    #[test]
+    fn start_veml6030_async() {
+        let address = addresses::DEFAULT;
+        let expectations = [
+            // Read once, then every field write starts from the shadow copy
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x00]),
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x00, 0x00]),
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x18, 0x00]),
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x1A, 0x00]),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+
+        block_on(VEML6030Async::build(i2c.clone(), address)).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn ambient_light_lux_async() {
+        let address = addresses::DEFAULT;
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::AMBIENT_LIGHT_DATA_REG], vec![0x00, 0x64]),
+            // Gain and integration time from one read of the setting register
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x00]),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut veml6030 = VEML6030Async::new(i2c.clone(), address);
+        let lux = block_on(veml6030.get_ambient_light_lux()).unwrap();
+
+        assert!((lux - 5.76).abs() < 1e-4);
+        i2c.done();
+    }
+
+    #[test]
+    fn auto_range_async() {
+        let address = addresses::DEFAULT;
+        let als = |raw: u16| I2cTransaction::write_read(address, vec![registers::AMBIENT_LIGHT_DATA_REG], raw.to_be_bytes().to_vec());
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x00]),
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x10, 0x00]),
+            als(0),
+            als(20000),
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x12, 0x00]),
+            als(0),
+            als(9000),
+        ];
+        let delays = [100, 100, 50, 50].map(DelayTransaction::async_delay_ms);
+        let mut i2c = I2cMock::new(&expectations);
+        let mut delay = CheckedDelay::new(&delays);
+
+        let mut veml6030 = VEML6030Async::new(i2c.clone(), address);
+        let reading = block_on(veml6030.get_ambient_light_lux_auto_ranged(&mut delay)).unwrap();
+
+        assert_eq!(reading.range, Range { gain: Gain::X1_8, integration_time: IntegrationTime::Ms50 });
+        i2c.done();
+        delay.done();
+    }
+}
@@ -0,0 +1,83 @@
+// Automatic gain and integration time selection, following Vishay's
+// "Designing the VEML6030 into an application" note: start at low
+// sensitivity, raise it while the count is too small to resolve and lower it
+// before the count saturates.
+
+use super::{Gain, IntegrationTime};
+
+// Counts below this have poor resolution, the next range up is used
+pub const LOW_COUNT: u16 = 100;
+// Counts above this are close to saturating, the next range down is used
+pub const HIGH_COUNT: u16 = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub gain: Gain,
+    pub integration_time: IntegrationTime
+}
+
+const fn range(gain: Gain, integration_time: IntegrationTime) -> Range {
+    Range { gain, integration_time }
+}
+
+// Least to most sensitive. Gain is raised before integration time so
+// readings stay fast, short times are only used in bright light.
+pub const RANGES: [Range; 9] = [
+    range(Gain::X1_8, IntegrationTime::Ms25),
+    range(Gain::X1_8, IntegrationTime::Ms50),
+    range(Gain::X1_8, IntegrationTime::Ms100),
+    range(Gain::X1_4, IntegrationTime::Ms100),
+    range(Gain::X1, IntegrationTime::Ms100),
+    range(Gain::X2, IntegrationTime::Ms100),
+    range(Gain::X2, IntegrationTime::Ms200),
+    range(Gain::X2, IntegrationTime::Ms400),
+    range(Gain::X2, IntegrationTime::Ms800),
+];
+
+// Where ranging starts, gain 1/8 and 100 ms as in the application note
+pub const START: usize = 2;
+
+// Range to switch to after reading `raw` counts at `RANGES[index]`, None
+// when the count is usable or there is no range further in that direction.
+pub fn next(index: usize, raw: u16) -> Option<usize> {
+    if raw < LOW_COUNT && index + 1 < RANGES.len() {
+        Some(index + 1)
+    } else if raw > HIGH_COUNT && index > 0 {
+        Some(index - 1)
+    } else {
+        None
+    }
+}
+
+// Lux reading and the range it was taken at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RangedLux {
+    pub lux: f32,
+    pub raw: u16,
+    pub range: Range
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::veml6030::resolution;
+
+    #[test]
+    fn ranges_grow_more_sensitive() {
+        let lux_per_count = RANGES.map(|range| resolution(range.gain, range.integration_time));
+        assert!(lux_per_count.windows(2).all(|pair| pair[1] < pair[0]));
+        assert_eq!(RANGES[START], Range { gain: Gain::X1_8, integration_time: IntegrationTime::Ms100 });
+    }
+
+    #[test]
+    fn steps_towards_usable_counts() {
+        assert_eq!(next(START, 50), Some(START + 1));
+        assert_eq!(next(START, 20000), Some(START - 1));
+        assert_eq!(next(START, LOW_COUNT), None);
+        assert_eq!(next(START, HIGH_COUNT), None);
+        // Nothing beyond the ends
+        assert_eq!(next(RANGES.len() - 1, 0), None);
+        assert_eq!(next(0, u16::MAX), None);
+    }
+}
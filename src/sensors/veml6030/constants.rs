@@ -11,4 +11,30 @@ pub mod registers {
 pub mod addresses {
     pub const DEFAULT: u8 = 0x48;
     pub const ALTERNATIVE: u8 = 0x10;
-}
\ No newline at end of file
+}
+
+// Bit fields within the 16 bit registers
+pub mod fields {
+    use super::registers;
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct Field {
+        pub register: u8,
+        pub trailing_zeros: u16,
+        pub length: u16
+    }
+
+    const fn field(register: u8, trailing_zeros: u16, length: u16) -> Field {
+        Field { register, trailing_zeros, length }
+    }
+
+    pub const GAIN: Field = field(registers::SETTING_REG, 11, 2);
+    pub const INTEGRATION_TIME: Field = field(registers::SETTING_REG, 6, 4);
+    pub const PERSISTENCE_PROTECT_NUMBER: Field = field(registers::SETTING_REG, 4, 2);
+    pub const INTERRUPT_ENABLE: Field = field(registers::SETTING_REG, 1, 1);
+    pub const SHUTDOWN: Field = field(registers::SETTING_REG, 0, 1);
+    pub const POWER_SAVING_MODE: Field = field(registers::POWER_SAVE_REG, 1, 2);
+    pub const POWER_SAVING_MODE_ENABLE: Field = field(registers::POWER_SAVE_REG, 0, 1);
+    pub const LOW_THRESHOLD_EXCEEDED: Field = field(registers::INTERRUPT_REG, 15, 1);
+    pub const HIGH_THRESHOLD_EXCEEDED: Field = field(registers::INTERRUPT_REG, 14, 1);
+}
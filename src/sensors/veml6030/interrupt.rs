@@ -0,0 +1,174 @@
+// Lux threshold interrupts. The chip compares each raw count against the
+// threshold windows and, after the persistence count of readings outside
+// them, pulls INT low until the interrupt register is read.
+
+use embedded_hal::i2c::I2c;
+
+use crate::protocols::gpio::InterruptPin;
+use super::constants::{fields, registers};
+use super::{clip_field, insert_field, lux_to_raw, InterruptEnable, PersistenceProtectNumber, VEML6030, VEML6030Error};
+
+// Thresholds in lux, before the high-lux correction of `compensate_lux`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LuxThresholds {
+    pub low: f32,
+    pub high: f32,
+    pub persistence: PersistenceProtectNumber  // Readings out of the window before INT fires
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdEvent {
+    BelowLow { lux: f32 },
+    AboveHigh { lux: f32 }
+}
+
+#[derive(Debug)]
+pub enum InterruptError<E, P> {
+    Sensor(VEML6030Error<E>),
+    Pin(P)  // The interrupt line failed, with the GPIO error
+}
+
+impl<E, P> From<VEML6030Error<E>> for InterruptError<E, P> {
+    fn from(error: VEML6030Error<E>) -> Self {
+        InterruptError::Sensor(error)
+    }
+}
+
+impl<I2C: I2c> VEML6030<I2C> {
+    // Write the thresholds as counts for the current gain and integration
+    // time, then enable the interrupt. Set them again after a range change.
+    pub fn enable_threshold_interrupt(&mut self, thresholds: LuxThresholds) -> Result<(), VEML6030Error<I2C::Error>> {
+        let range = self.get_range()?;
+        self.set_low_threshold_window(lux_to_raw(thresholds.low, range.gain, range.integration_time))?;
+        self.set_high_threshold_window(lux_to_raw(thresholds.high, range.gain, range.integration_time))?;
+
+        // Drop an interrupt left over from the previous thresholds
+        self.read_and_convert_to_u16(registers::INTERRUPT_REG)?;
+        let setting = self.read_register(registers::SETTING_REG)?;
+        let setting = insert_field(setting, fields::PERSISTENCE_PROTECT_NUMBER, thresholds.persistence.into());
+        let setting = insert_field(setting, fields::INTERRUPT_ENABLE, InterruptEnable::Enable.into());
+        self.convert_and_write_u16(registers::SETTING_REG, setting)
+    }
+
+    pub fn disable_threshold_interrupt(&mut self) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.set_interrupt_enabled(InterruptEnable::Disable)
+    }
+
+    // Read and clear the interrupt status, None if no threshold was crossed
+    pub fn read_threshold_event(&mut self) -> Result<Option<ThresholdEvent>, VEML6030Error<I2C::Error>> {
+        let status = self.read_and_convert_to_u16(registers::INTERRUPT_REG)?;
+        let high = clip_field(status, fields::HIGH_THRESHOLD_EXCEEDED) == 1;
+        let low = clip_field(status, fields::LOW_THRESHOLD_EXCEEDED) == 1;
+        if !high && !low {
+            return Ok(None)
+        }
+
+        let lux = self.get_ambient_light_lux()?;
+        Ok(Some(if high { ThresholdEvent::AboveHigh { lux } } else { ThresholdEvent::BelowLow { lux } }))
+    }
+
+    // Block on the INT line until a threshold is crossed. Edges without a
+    // pending interrupt, e.g. from another sensor sharing the line, are skipped.
+    pub fn wait_for_threshold_event<P: InterruptPin>(&mut self, pin: &mut P) -> Result<ThresholdEvent, InterruptError<I2C::Error, P::Error>> {
+        loop {
+            pin.wait_for_interrupt().map_err(InterruptError::Pin)?;
+            if let Some(event) = self.read_threshold_event()? {
+                return Ok(event)
+            }
+        }
+    }
+
+    // Stream of threshold events. Sensor errors are yielded and the stream
+    // goes on, a pin error is yielded last: a dead line would fail forever.
+    pub fn threshold_events<'a, P: InterruptPin>(&'a mut self, pin: &'a mut P) -> impl Iterator<Item = Result<ThresholdEvent, InterruptError<I2C::Error, P::Error>>> + 'a {
+        let mut pin_failed = false;
+        core::iter::from_fn(move || {
+            if pin_failed {
+                return None
+            }
+            let event = self.wait_for_threshold_event(pin);
+            pin_failed = matches!(event, Err(InterruptError::Pin(_)));
+            Some(event)
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use super::*;
+    use crate::sensors::veml6030::Address;
+
+    // Interrupt line that fires a set number of times, then fails
+    struct MockPin {
+        interrupts: usize
+    }
+
+    impl InterruptPin for MockPin {
+        type Error = &'static str;
+
+        fn wait_for_interrupt(&mut self) -> Result<(), Self::Error> {
+            if self.interrupts == 0 {
+                return Err("no more interrupts")
+            }
+            self.interrupts -= 1;
+            Ok(())
+        }
+    }
+
+    fn status(address: u8, bytes: [u8; 2]) -> I2cTransaction {
+        I2cTransaction::write_read(address, vec![registers::INTERRUPT_REG], bytes.to_vec())
+    }
+
+    fn lux_reading(address: u8, raw: u16) -> I2cTransaction {
+        I2cTransaction::write_read(address, vec![registers::AMBIENT_LIGHT_DATA_REG], raw.to_be_bytes().to_vec())
+    }
+
+    #[test]
+    fn thresholds_in_lux() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x00]),
+            // 57.6 lux and 576 lux at 0.0576 lux per count
+            I2cTransaction::write(address, vec![registers::L_THRESH_REG, 0x03, 0xE8]),
+            I2cTransaction::write(address, vec![registers::H_THRESH_REG, 0x27, 0x10]),
+            status(address, [0x40, 0x00]),
+            // Persistence 4 and interrupt enable in one write
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x00, 0x22]),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut veml6030 = VEML6030::new(i2c.clone(), address);
+        veml6030.enable_threshold_interrupt(LuxThresholds { low: 57.61, high: 576.01, persistence: PersistenceProtectNumber::N4 }).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn typed_events_from_the_pin() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            status(address, [0x80, 0x00]),
+            lux_reading(address, 100),
+            // Lux conversion at gain x1, 100 ms: 0.0576 lux per count
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x00]),
+            // Spurious edge, then a high crossing, converted from the shadow copy
+            status(address, [0x00, 0x00]),
+            status(address, [0x40, 0x00]),
+            lux_reading(address, 20000),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+        let mut pin = MockPin { interrupts: 3 };
+
+        let mut veml6030 = VEML6030::new(i2c.clone(), address);
+        // Ends with the pin error
+        let events: Vec<_> = veml6030.threshold_events(&mut pin).collect();
+
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[0], Ok(ThresholdEvent::BelowLow { lux }) if (lux - 5.76).abs() < 1e-4));
+        assert!(matches!(events[1], Ok(ThresholdEvent::AboveHigh { lux }) if lux > 1152.0));
+        assert!(matches!(events[2], Err(InterruptError::Pin("no more interrupts"))));
+        i2c.done();
+    }
+}
@@ -0,0 +1,201 @@
+// Refresh time and supply current, after the datasheet table "Refresh time,
+// ALS_IT, PSM and current consumption". With power saving on the chip
+// integrates once, then idles for the PSM wait time, so its current is the
+// active draw averaged over that cycle. Gain does not change either figure,
+// it only trades resolution against the brightest measurable light.
+
+use embedded_hal::i2c::I2c;
+
+use super::autorange::Range;
+use super::constants::{fields, registers};
+use super::{clip_field, decode, decode_range, insert_field, resolution};
+use super::{Gain, IntegrationTime, PowerSavingMode, PowerSavingModeEnable, VEML6030, VEML6030Error};
+
+// Typical supply current at 3.3 V while integrating, in µA
+pub const ACTIVE_CURRENT: f32 = 45.0;
+// Typical supply current between integrations, in µA
+pub const IDLE_CURRENT: f32 = 0.5;
+
+impl PowerSavingMode {
+    // Idle time after each integration
+    pub fn wait_millis(&self) -> u32 {
+        match self {
+            PowerSavingMode::M1 => 500,
+            PowerSavingMode::M2 => 1000,
+            PowerSavingMode::M3 => 2000,
+            PowerSavingMode::M4 => 4000
+        }
+    }
+}
+
+const GAINS: [Gain; 4] = [Gain::X1_8, Gain::X1_4, Gain::X1, Gain::X2];
+const INTEGRATION_TIMES: [IntegrationTime; 6] = [
+    IntegrationTime::Ms25,
+    IntegrationTime::Ms50,
+    IntegrationTime::Ms100,
+    IntegrationTime::Ms200,
+    IntegrationTime::Ms400,
+    IntegrationTime::Ms800,
+];
+const POWER_SAVING: [Option<PowerSavingMode>; 5] = [
+    None,
+    Some(PowerSavingMode::M1),
+    Some(PowerSavingMode::M2),
+    Some(PowerSavingMode::M3),
+    Some(PowerSavingMode::M4),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerProfile {
+    pub range: Range,
+    pub power_saving: Option<PowerSavingMode>  // None converts back to back
+}
+
+impl PowerProfile {
+    // Time between new readings, the shortest sensible polling interval
+    pub fn refresh_millis(&self) -> u32 {
+        let wait = self.power_saving.map_or(0, |mode| mode.wait_millis());
+        self.range.integration_time.millis() + wait
+    }
+
+    // Average supply current in µA
+    pub fn supply_current(&self) -> f32 {
+        let active = self.range.integration_time.millis() as f32;
+        let refresh = self.refresh_millis() as f32;
+        (ACTIVE_CURRENT * active + IDLE_CURRENT * (refresh - active)) / refresh
+    }
+
+    pub fn lux_per_count(&self) -> f32 {
+        resolution(self.range.gain, self.range.integration_time)
+    }
+}
+
+// Every gain, integration time and power saving combination
+pub fn profiles() -> impl Iterator<Item = PowerProfile> {
+    POWER_SAVING.into_iter().flat_map(|power_saving| {
+        INTEGRATION_TIMES.into_iter().flat_map(move |integration_time| {
+            GAINS.into_iter().map(move |gain| PowerProfile { range: Range { gain, integration_time }, power_saving })
+        })
+    })
+}
+
+// Least current profile with a new reading at least every `interval_ms` and
+// at most `max_lux_per_count` per count. Of equally frugal profiles the least
+// sensitive wins, it saturates last. None when no profile refreshes that fast.
+pub fn lowest_power(interval_ms: u32, max_lux_per_count: f32) -> Option<PowerProfile> {
+    profiles()
+        .filter(|profile| profile.refresh_millis() <= interval_ms && profile.lux_per_count() <= max_lux_per_count)
+        .min_by(|a, b| {
+            a.supply_current().total_cmp(&b.supply_current())
+                .then(b.lux_per_count().total_cmp(&a.lux_per_count()))
+        })
+}
+
+impl<I2C: I2c> VEML6030<I2C> {
+    pub fn get_power_profile(&mut self) -> Result<PowerProfile, VEML6030Error<I2C::Error>> {
+        let range = decode_range(self.read_register(registers::SETTING_REG)?)?;
+        let power_save = self.read_register(registers::POWER_SAVE_REG)?;
+        let enabled: PowerSavingModeEnable = decode(registers::POWER_SAVE_REG, clip_field(power_save, fields::POWER_SAVING_MODE_ENABLE))?;
+        let power_saving = match enabled {
+            PowerSavingModeEnable::Enable => Some(decode(registers::POWER_SAVE_REG, clip_field(power_save, fields::POWER_SAVING_MODE))?),
+            PowerSavingModeEnable::Disable => None
+        };
+        Ok(PowerProfile { range, power_saving })
+    }
+
+    // One write per register. Auto-ranging is turned off, it would change
+    // the range and so the resolution and current.
+    pub fn set_power_profile(&mut self, profile: PowerProfile) -> Result<(), VEML6030Error<I2C::Error>> {
+        self.disable_auto_range();
+        self.set_range(profile.range)?;
+
+        let power_save = self.read_register(registers::POWER_SAVE_REG)?;
+        let power_save = match profile.power_saving {
+            Some(mode) => {
+                let power_save = insert_field(power_save, fields::POWER_SAVING_MODE, mode.into());
+                insert_field(power_save, fields::POWER_SAVING_MODE_ENABLE, PowerSavingModeEnable::Enable.into())
+            },
+            None => insert_field(power_save, fields::POWER_SAVING_MODE_ENABLE, PowerSavingModeEnable::Disable.into())
+        };
+        self.convert_and_write_u16(registers::POWER_SAVE_REG, power_save)
+    }
+
+    // Switch to the `lowest_power` profile for sampling every `interval_ms`.
+    // Nothing is written and None returned if the chip cannot keep up.
+    pub fn set_sample_interval(&mut self, interval_ms: u32, max_lux_per_count: f32) -> Result<Option<PowerProfile>, VEML6030Error<I2C::Error>> {
+        let Some(profile) = lowest_power(interval_ms, max_lux_per_count) else {
+            return Ok(None)
+        };
+        self.set_power_profile(profile)?;
+        Ok(Some(profile))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal_mock::eh1::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+
+    use super::*;
+    use crate::sensors::veml6030::Address;
+
+    fn profile(gain: Gain, integration_time: IntegrationTime, power_saving: Option<PowerSavingMode>) -> PowerProfile {
+        PowerProfile { range: Range { gain, integration_time }, power_saving }
+    }
+
+    #[test]
+    fn matches_datasheet_table() {
+        use IntegrationTime::*;
+        use PowerSavingMode::*;
+        // Integration time, mode, refresh time in ms and current in µA
+        let table = [
+            (Ms100, M1, 600, 8.0), (Ms100, M2, 1100, 5.0), (Ms100, M3, 2100, 3.0), (Ms100, M4, 4100, 2.0),
+            (Ms200, M1, 700, 13.0), (Ms200, M2, 1200, 8.0), (Ms200, M3, 2200, 5.0), (Ms200, M4, 4200, 3.0),
+            (Ms400, M1, 900, 20.0), (Ms400, M2, 1400, 13.0), (Ms400, M3, 2400, 8.0), (Ms400, M4, 4400, 5.0),
+            (Ms800, M1, 1300, 28.0), (Ms800, M2, 1800, 20.0), (Ms800, M3, 2800, 13.0), (Ms800, M4, 4800, 8.0),
+        ];
+        for (integration_time, mode, refresh, current) in table {
+            let profile = profile(Gain::X1, integration_time, Some(mode));
+            assert_eq!(profile.refresh_millis(), refresh);
+            // The datasheet rounds to whole µA
+            assert!((profile.supply_current() - current).abs() <= 0.5, "{:?}", profile);
+        }
+
+        let continuous = profile(Gain::X2, Ms100, None);
+        assert_eq!((continuous.refresh_millis(), continuous.supply_current()), (100, ACTIVE_CURRENT));
+    }
+
+    #[test]
+    fn lowest_power_for_interval() {
+        // 0.0576 lux per count is x1 at 100 ms or x2 at 50 ms, the shorter
+        // integration costs less
+        assert_eq!(lowest_power(1000, 0.0576), Some(profile(Gain::X2, IntegrationTime::Ms50, Some(PowerSavingMode::M1))));
+        // No resolution requirement: shortest integration, longest wait, least gain
+        assert_eq!(lowest_power(5000, f32::MAX), Some(profile(Gain::X1_8, IntegrationTime::Ms25, Some(PowerSavingMode::M4))));
+        assert_eq!(lowest_power(30, f32::MAX), Some(profile(Gain::X1_8, IntegrationTime::Ms25, None)));
+        assert_eq!(lowest_power(20, f32::MAX), None);
+        assert_eq!(lowest_power(5000, 0.001), None);
+    }
+
+    #[test]
+    fn sample_interval_written_once_per_register() {
+        let address: u8 = Address::Default.into();
+        let expectations = [
+            I2cTransaction::write_read(address, vec![registers::SETTING_REG], vec![0x00, 0x01]),
+            // x2, 50 ms, still shut down
+            I2cTransaction::write(address, vec![registers::SETTING_REG, 0x0A, 0x01]),
+            I2cTransaction::write_read(address, vec![registers::POWER_SAVE_REG], vec![0x00, 0x06]),
+            // Mode 1, enabled
+            I2cTransaction::write(address, vec![registers::POWER_SAVE_REG, 0x00, 0x01]),
+        ];
+        let mut i2c = I2cMock::new(&expectations);
+
+        let mut veml6030 = VEML6030::new(i2c.clone(), address);
+        let profile = veml6030.set_sample_interval(1000, 0.0576).unwrap();
+        assert_eq!(profile.map(|profile| profile.refresh_millis()), Some(550));
+        // Read back from the shadow copies
+        assert_eq!(veml6030.get_power_profile().unwrap(), profile.unwrap());
+        assert_eq!(veml6030.set_sample_interval(10, 0.0576).unwrap(), None);
+        i2c.done();
+    }
+}